@@ -42,6 +42,7 @@ struct MyProgram {
 impl Application for MyProgram {
     type Message = Message;
     type Flags = Flags;
+    type Output = ();
     type Theme = Theme;
     type Executor = iced_baseview::executor::Default;
 
@@ -40,6 +40,7 @@ struct MyProgram {
 impl Application for MyProgram {
     type Message = Message;
     type Flags = ();
+    type Output = ();
     type Theme = Theme;
     type Executor = iced_baseview::executor::Default;
 
@@ -1,6 +1,9 @@
 //! Create interactive, native cross-platform applications.
+mod damage;
+mod overlay;
 #[cfg(feature = "trace")]
 mod profiler;
+mod scroll;
 mod state;
 
 use baseview::EventStatus;
@@ -12,9 +15,11 @@ use iced_widget::Theme;
 use raw_window_handle::HasRawDisplayHandle;
 pub use state::State;
 
+use crate::core::event;
 use crate::core::mouse;
 use crate::core::renderer;
 use crate::core::widget::operation;
+use crate::core::window;
 use crate::core::Size;
 use crate::futures::futures;
 use crate::futures::{Executor, Runtime, Subscription};
@@ -22,19 +27,28 @@ use crate::graphics::compositor::{self, Compositor};
 use crate::runtime::clipboard;
 use crate::runtime::user_interface::{self, UserInterface};
 use crate::runtime::Debug;
-use crate::window::{IcedWindow, RuntimeEvent, WindowQueue, WindowSubs};
+use crate::settings::IcedBaseviewSettings;
+use crate::window::{
+    FrameMetrics, IcedWindow, RuntimeEvent, Shared, SizeLimits, WindowCommand, WindowQueue,
+    WindowRequest, WindowSubs,
+};
 use crate::{Clipboard, Error, Proxy, Renderer, Settings};
 
 use futures::channel::mpsc;
 
-use std::cell::RefCell;
-use std::mem::ManuallyDrop;
-use std::rc::Rc;
+use std::any::Any;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use damage::Damage;
+use overlay::Overlay;
+use scroll::Scroll;
 
 #[cfg(feature = "trace")]
 pub use profiler::Profiler;
 #[cfg(feature = "trace")]
-use tracing::{info_span, instrument::Instrument};
+use tracing::info_span;
 
 /// An interactive, native cross-platform application.
 ///
@@ -69,6 +83,17 @@ where
     /// The data needed to initialize your [`Application`].
     type Flags;
 
+    /// The type of the values your [`Application`] sends out of the window
+    /// with [`window::emit`], like parameter gestures or requests to save
+    /// state. They are handed to the callback passed to
+    /// [`open_parented_with_output`].
+    ///
+    /// Use `()` if your [`Application`] doesn't emit anything.
+    ///
+    /// [`window::emit`]: crate::window::emit
+    /// [`open_parented_with_output`]: crate::open_parented_with_output
+    type Output: Send + 'static;
+
     /// Initializes the [`Application`] with the flags provided to
     /// [`run`] as part of the [`Settings`].
     ///
@@ -97,6 +122,24 @@ where
     /// runtime.
     fn update(&mut self, message: Self::Message) -> Task<Self::Message>;
 
+    /// Handles a __message__ without rebuilding the user interface.
+    ///
+    /// This is called for every message as soon as it's produced. Return
+    /// `None` if the message was handled here, in which case the runtime keeps
+    /// the current user interface and only redraws it. Messages that are
+    /// returned are passed on to [`update`](#tymethod.update) as usual.
+    ///
+    /// Since the user interface borrows the [`Application`], this only gets a
+    /// shared reference. Any state changed here has to live behind interior
+    /// mutability, like a `Cell` or an atomic, and is only visible through
+    /// widgets that read it while drawing. This makes it a good fit for
+    /// messages that arrive constantly, like parameter changes sent by a host.
+    ///
+    /// By default, every message is passed on.
+    fn update_in_place(&self, message: Self::Message) -> Option<Self::Message> {
+        Some(message)
+    }
+
     /// Returns the widgets to display in the [`Application`] for the main window.
     ///
     /// These widgets can produce __messages__ based on user interaction.
@@ -136,12 +179,62 @@ where
         baseview::WindowScalePolicy::SystemScaleFactor
     }
 
+    /// Returns the smallest logical size the window may be resized to.
+    ///
+    /// By default, there is no limit.
+    fn min_size(&self) -> Option<Size> {
+        None
+    }
+
+    /// Returns the largest logical size the window may be resized to.
+    ///
+    /// By default, there is no limit.
+    fn max_size(&self) -> Option<Size> {
+        None
+    }
+
+    /// Returns the ratio of width to height the window must keep.
+    ///
+    /// By default, the window may have any aspect ratio.
+    fn aspect_ratio(&self) -> Option<f32> {
+        None
+    }
+
+    /// Returns the logical size the window takes when `requested` is asked
+    /// for, by the application through [`window::resize`], by the user or by
+    /// the host.
+    ///
+    /// By default, this applies [`min_size`], [`max_size`] and
+    /// [`aspect_ratio`] as described by [`SizeLimits::constrain`].
+    ///
+    /// [`WindowHandle::constrain_size`] answers the host right away on its own
+    /// thread, so it only knows about these limits. A size returned by an
+    /// overridden `constrain_size` is applied once the window handles the
+    /// resize.
+    ///
+    /// [`window::resize`]: crate::window::resize
+    /// [`min_size`]: Self::min_size
+    /// [`max_size`]: Self::max_size
+    /// [`aspect_ratio`]: Self::aspect_ratio
+    /// [`WindowHandle::constrain_size`]: crate::window::WindowHandle::constrain_size
+    fn constrain_size(&self, requested: Size) -> Size {
+        SizeLimits::of(self).constrain(requested)
+    }
+
     /// Ignore non-modifier keyboard keys. Overrides the field in
     /// `IcedBaseviewSettings` if set
     fn ignore_non_modifier_keys(&self) -> Option<bool> {
         None
     }
 
+    /// Parses a __message__ sent through the socket of the `automation`
+    /// feature, see [`automation`](crate::automation).
+    ///
+    /// By default, no messages are accepted.
+    fn automation_message(&self, _text: &str) -> Option<Self::Message> {
+        None
+    }
+
     //fn renderer_settings() -> crate::renderer::Settings;
 }
 
@@ -183,24 +276,24 @@ pub(crate) fn run<A, C>(
     window: &mut baseview::Window<'_>,
     flags: A::Flags,
     settings: Settings,
-    event_sender: mpsc::UnboundedSender<RuntimeEvent<A::Message>>,
-    event_receiver: mpsc::UnboundedReceiver<RuntimeEvent<A::Message>>,
-) -> Result<IcedWindow<A>, Error>
+    handle_receiver: mpsc::UnboundedReceiver<RuntimeEvent<A::Message>>,
+    shared: Arc<Shared>,
+    on_output: Box<dyn FnMut(A::Output) + Send>,
+) -> Result<IcedWindow<A, C>, Error>
 where
     A: Application + 'static + Send,
+    A::Flags: Send,
     C: Compositor<Renderer = Renderer> + 'static,
     A::Theme: DefaultStyle,
 {
-    use futures::task;
-
     #[cfg(feature = "trace")]
-    let _guard = Profiler::init();
+    let _ = Profiler::init(settings.trace_file.as_deref());
 
     let mut debug = Debug::new();
     debug.startup_started();
 
     #[cfg(feature = "trace")]
-    let _ = info_span!("Application", "RUN").entered();
+    let _run_span = info_span!("Application", "RUN").entered();
 
     let viewport = {
         // Assume scale for now until there is an event with a new one.
@@ -228,9 +321,8 @@ where
 
     let (application, init_task) = runtime.enter(|| A::new(flags));
 
-    if let Some(stream) = crate::runtime::task::into_stream(init_task) {
-        runtime.run(stream);
-    }
+    let mut init_actions = Vec::new();
+    spawn(&mut runtime, init_task, &mut init_actions);
 
     let mut window_subs = WindowSubs::default();
 
@@ -258,386 +350,865 @@ where
         compositor.load_font(font);
     }
 
-    let (window_queue, window_queue_rx) = WindowQueue::new();
-    let event_status = Rc::new(RefCell::new(baseview::EventStatus::Ignored));
-
     let state = State::new(&application, viewport);
 
     let display_handle = crate::conversion::convert_raw_display_handle(window.raw_display_handle());
     let clipboard = Clipboard::new(display_handle);
 
-    let instance = Box::pin({
-        let run_instance = run_instance::<A, C>(
-            application,
-            compositor,
-            renderer,
-            debug,
-            runtime,
-            event_receiver,
-            clipboard,
-            window_subs,
-            settings.iced_baseview,
-            surface,
-            event_status.clone(),
-            state,
-            window_queue,
-        );
+    #[cfg(all(unix, feature = "automation"))]
+    let automation_socket = settings.automation_socket;
+    let settings = settings.iced_baseview;
+
+    let mut instance = Instance {
+        user_interface: None,
+        cache: Some(user_interface::Cache::default()),
+        application: Box::new(application),
+        compositor,
+        renderer,
+        surface,
+        debug,
+        runtime,
+        clipboard,
+        window_subs,
+        settings,
+        viewport_version: state.viewport_version(),
+        state,
+        window_queue: WindowQueue::new(),
+        scroll: Scroll::new(settings.scroll),
+        damage: Damage::default(),
+        overlay: Overlay::new(settings.debug),
+        params_seen: Vec::new(),
+        metrics: FrameMetrics::default(),
+        events: Vec::new(),
+        messages: Vec::new(),
+        mouse_interaction: mouse::Interaction::default(),
+        needs_redraw: true,
+        redraw_at: None,
+        needs_update: true,
+        // Make sure the first frame gets drawn.
+        did_process_event: true,
+        suspended: false,
+        pending_operations: Vec::new(),
+        window_id: crate::window::Id::unique(),
+        shared,
+    };
 
-        #[cfg(feature = "trace")]
-        let run_instance = run_instance.instrument(info_span!("Application", "LOOP"));
+    for action in init_actions {
+        instance.handle_action(action);
+    }
+
+    instance.publish_size();
+    instance.publish_size_limits();
 
-        run_instance
-    });
+    instance.debug.startup_finished();
 
-    let runtime_context = task::Context::from_waker(task::noop_waker_ref());
+    let mut iced_window =
+        IcedWindow::new(instance, runtime_rx, handle_receiver, on_output, &settings);
 
-    Ok(IcedWindow {
-        sender: event_sender,
-        instance,
-        runtime_context,
-        runtime_rx,
-        window_queue_rx,
-        event_status,
+    // Baseview can't limit the size of the window, so it has to be resized
+    // if it was opened with a size the application doesn't allow.
+    iced_window.apply_size_limits(window);
 
-        processed_close_signal: false,
-    })
+    #[cfg(all(unix, feature = "automation"))]
+    if let Some(path) = &automation_socket {
+        iced_window.bind_automation(path);
+    }
+
+    Ok(iced_window)
 }
 
-async fn run_instance<A, C>(
-    mut application: A,
-    mut compositor: C,
-    mut renderer: Renderer,
-    mut debug: Debug,
-    mut runtime: Runtime<A::Executor, Proxy<A::Message>, iced_runtime::Action<A::Message>>,
-    mut event_receiver: mpsc::UnboundedReceiver<RuntimeEvent<A::Message>>,
-    mut clipboard: Clipboard,
-    mut window_subs: WindowSubs<<A as Application>::Message>,
-
-    settings: crate::settings::IcedBaseviewSettings,
-    mut surface: C::Surface,
-    event_status: Rc<RefCell<baseview::EventStatus>>,
-    mut state: State<A>,
-    mut window_queue: WindowQueue,
-) where
-    // What an absolute monstrosity of generics.
+/// A running [`Application`] and everything needed to render it.
+///
+/// The `baseview` window drives the instance by calling its methods directly
+/// from its [`WindowHandler`](baseview::WindowHandler) implementation.
+#[allow(missing_debug_implementations)]
+pub(crate) struct Instance<A, C>
+where
+    A: Application + 'static,
     C: Compositor<Renderer = Renderer> + 'static,
+    A::Theme: DefaultStyle,
+{
+    // The user interface borrows `application`, so it needs to be dropped first.
+    user_interface: Option<UserInterface<'static, A::Message, A::Theme, Renderer>>,
+    cache: Option<user_interface::Cache>,
+    application: Box<A>,
+
+    compositor: C,
+    renderer: Renderer,
+    surface: C::Surface,
+    debug: Debug,
+    runtime: Runtime<A::Executor, Proxy<A::Message>, Action<A::Message>>,
+    clipboard: Clipboard,
+    window_subs: WindowSubs<A::Message>,
+    settings: IcedBaseviewSettings,
+    state: State<A>,
+    window_queue: WindowQueue,
+    scroll: Scroll,
+    damage: Damage,
+    overlay: Overlay,
+    // The bits of the `WindowSubs::params` values on the last frame
+    params_seen: Vec<Option<u32>>,
+    // The timings of the frame in progress
+    metrics: FrameMetrics,
+
+    events: Vec<crate::core::Event>,
+    messages: Vec<A::Message>,
+    mouse_interaction: mouse::Interaction,
+    viewport_version: usize,
+
+    // Set whenever the user interface was drawn and the result hasn't been presented yet
+    needs_redraw: bool,
+    // When a widget asked to be redrawn, for instance to animate
+    redraw_at: Option<Instant>,
+    // May be triggered when processing baseview events, will cause the UI to be updated in the next
+    // frame
+    needs_update: bool,
+    did_process_event: bool,
+    // Set while the host hides the window, see `WindowHandle::suspend`
+    suspended: bool,
+    // The widget operations received while suspended
+    pending_operations: Vec<Box<dyn operation::Operation>>,
+
+    window_id: crate::window::Id,
+    shared: Arc<Shared>,
+}
+
+impl<A, C> Instance<A, C>
+where
     A: Application + 'static,
+    C: Compositor<Renderer = Renderer> + 'static,
     A::Theme: DefaultStyle,
 {
-    use futures::stream::StreamExt;
+    /// Processes a `baseview` event and returns whether the application
+    /// captured it.
+    ///
+    /// Cursor movements and wheel scrolls are queued until the next frame when
+    /// input coalescing is enabled, and smoothed wheel scrolls are spread over
+    /// the next frames. No widget has seen them yet, so those are reported as
+    /// ignored.
+    pub fn handle_event(&mut self, event: baseview::Event) -> EventStatus {
+        self.state.update(&event);
+
+        let ignore_non_modifier_keys = self
+            .application
+            .ignore_non_modifier_keys()
+            .unwrap_or(self.settings.ignore_non_modifier_keys);
+
+        // The window isn't drawn while suspended, so widgets don't get to see
+        // any input. The held buttons and modifiers are still tracked.
+        if self.suspended {
+            self.state
+                .convert_event(event, &mut Vec::new(), ignore_non_modifier_keys);
+
+            return EventStatus::Ignored;
+        }
 
-    let mut viewport_version = state.viewport_version();
+        if self.overlay.is_toggle_shortcut(&event) {
+            self.toggle_debug();
+        }
 
-    let cache = user_interface::Cache::default();
-    let mut events = Vec::new();
-    let mut messages = Vec::new();
+        let deferred = self.settings.coalesce_input
+            && matches!(
+                event,
+                baseview::Event::Mouse(
+                    baseview::MouseEvent::CursorMoved { .. }
+                        | baseview::MouseEvent::WheelScrolled { .. }
+                )
+            );
 
-    let mut user_interface = ManuallyDrop::new(build_user_interface(
-        &application,
-        cache,
-        &mut renderer,
-        state.logical_size(),
-        &mut debug,
-    ));
+        let first_event = self.events.len();
 
-    let mut mouse_interaction = mouse::Interaction::default();
+        self.state
+            .convert_event(event, &mut self.events, ignore_non_modifier_keys);
+        let _ = self.scroll.process(&mut self.events, first_event);
 
-    // Triggered whenever a baseview event gets sent
-    let mut redraw_requested = true;
-    // May be triggered when processing baseview events, will cause the UI to be updated in the next
-    // frame
-    let mut needs_update = true;
-    let mut did_process_event = false;
+        if deferred {
+            coalesce_events(&mut self.events, first_event);
+            self.did_process_event = true;
 
-    debug.startup_finished();
+            return EventStatus::Ignored;
+        }
 
-    let window_id = crate::window::Id::unique();
+        // Smoothed wheel scrolls are delivered over the next frames, so no
+        // widget has seen them yet either.
+        if self.events.len() == first_event {
+            return EventStatus::Ignored;
+        }
 
-    loop {
-        // Empty the queue if possible
-        let event = if let Ok(event) = event_receiver.try_next() {
-            event
-        } else {
-            event_receiver.next().await
-        };
+        self.did_process_event = true;
 
-        let Some(event) = event else {
-            break;
-        };
+        if self.process_events(first_event) {
+            EventStatus::Captured
+        } else {
+            EventStatus::Ignored
+        }
+    }
 
-        match event {
-            RuntimeEvent::MainEventsCleared => {
-                if let Some(message) = &window_subs.on_frame {
-                    if let Some(message) = message() {
-                        messages.push(message);
+    /// Handles an [`Action`] produced by a [`Task`], a subscription or the
+    /// [`WindowHandle`](crate::window::WindowHandle).
+    ///
+    /// While the window is suspended, widget operations wait until it's
+    /// resumed.
+    pub fn handle_action(&mut self, action: Action<A::Message>) {
+        match action {
+            Action::Output(message) => {
+                self.messages.push(message);
+            }
+            Action::Widget(mut operation) => {
+                if let Some(request) = WindowRequest::take(operation.as_mut()) {
+                    self.window_queue.request(request);
+                } else if self.suspended {
+                    self.pending_operations.push(operation);
+                } else {
+                    self.ensure_user_interface();
+
+                    if let Some(user_interface) = self.user_interface.as_mut() {
+                        let _ = run_operation(user_interface, &self.renderer, operation);
                     }
                 }
+            }
+            action => {
+                run_action::<A, C>(
+                    action,
+                    &mut self.compositor,
+                    &mut self.clipboard,
+                    &mut self.window_queue,
+                );
+            }
+        }
+    }
 
-                if !did_process_event
-                    && events.is_empty()
-                    && messages.is_empty()
-                    && !settings.always_redraw
-                {
-                    continue;
-                }
-                did_process_event = false;
+    /// Updates the application with the messages produced since the last
+    /// frame, and draws the user interface.
+    pub fn frame(&mut self) {
+        if let Some(message) = &self.window_subs.on_frame {
+            if let Some(message) = message() {
+                self.messages.push(message);
+            }
+        }
 
-                if !events.is_empty() {
-                    debug.event_processing_started();
+        self.check_params();
 
-                    let (interface_state, statuses) = user_interface.update(
-                        &events,
-                        state.cursor(),
-                        &mut renderer,
-                        &mut clipboard,
-                        &mut messages,
-                    );
+        // Messages are still handled while suspended, but the user interface
+        // is neither rebuilt nor drawn.
+        if self.suspended {
+            self.update_in_place();
 
-                    needs_update |= matches!(interface_state, user_interface::State::Outdated,);
+            if !self.messages.is_empty() {
+                self.update_application();
+            }
 
-                    debug.event_processing_finished();
+            return;
+        }
 
-                    for (event, status) in events.drain(..).zip(statuses.into_iter()) {
-                        runtime.broadcast(crate::futures::subscription::Event::Interaction {
-                            window: window_id,
-                            event,
-                            status,
-                        });
-                    }
-                }
+        let now = Instant::now();
 
-                // The user interface update may have pushed a new message onto the stack
-                needs_update |= !messages.is_empty() || settings.always_redraw;
+        if let Some(event) = self.scroll.frame(now) {
+            self.events.push(event);
+        }
 
-                if needs_update {
-                    needs_update = false;
+        if self.redraw_at.is_some_and(|redraw_at| redraw_at <= now) {
+            self.redraw_at = None;
 
-                    let cache = ManuallyDrop::into_inner(user_interface).into_cache();
+            self.events
+                .push(crate::core::Event::Window(window::Event::RedrawRequested(
+                    now,
+                )));
+        }
 
-                    // Update application
-                    update(
-                        &mut application,
-                        &mut runtime,
-                        &mut debug,
-                        &mut messages,
-                        &mut window_subs,
-                        //&mut window_queue,
-                    );
+        if !self.did_process_event
+            && self.events.is_empty()
+            && self.messages.is_empty()
+            && !self.settings.always_redraw
+        {
+            return;
+        }
+        self.did_process_event = false;
 
-                    // Update window
-                    state.synchronize(&application);
+        let _ = self.process_events(self.events.len());
 
-                    let should_exit = false; // FIXME
+        // Messages handled in place don't invalidate the user interface
+        self.update_in_place();
 
-                    user_interface = ManuallyDrop::new(build_user_interface(
-                        &application,
-                        cache,
-                        &mut renderer,
-                        state.logical_size(),
-                        &mut debug,
-                    ));
+        // The user interface update may have pushed a new message onto the stack
+        self.needs_update |= !self.messages.is_empty() || self.settings.always_redraw;
 
-                    if should_exit {
-                        break;
-                    }
-                }
+        if self.needs_update {
+            self.needs_update = false;
 
-                debug.draw_started();
-                let new_mouse_interaction = user_interface.draw(
-                    &mut renderer,
-                    state.theme(),
-                    &iced_runtime::core::renderer::Style {
-                        text_color: state.text_color(),
-                    },
-                    state.cursor(),
-                );
-                debug.draw_finished();
-
-                if new_mouse_interaction != mouse_interaction {
-                    // TODO: Set mouse cursor for MacOS once baseview supports it.
-                    #[cfg(not(target_os = "macos"))]
-                    if let Err(_) = window_queue.set_mouse_cursor(
-                        crate::conversion::convert_mouse_interaction(new_mouse_interaction),
-                    ) {
-                        debug.log_message(&"could not send set_mouse_cursor command".to_string());
-                    }
+            self.update_application();
+        }
 
-                    mouse_interaction = new_mouse_interaction;
-                }
+        self.draw();
+
+        self.needs_redraw = true;
+    }
+
+    /// Presents the last drawn frame.
+    pub fn redraw(&mut self) {
+        #[cfg(feature = "trace")]
+        let _frame_span = info_span!("Application", "FRAME").entered();
+
+        if self.suspended {
+            return;
+        }
 
-                redraw_requested = true;
+        let physical_size = self.state.physical_size();
+        let current_viewport_version = self.state.viewport_version();
+        let viewport_changed = self.viewport_version != current_viewport_version;
+
+        // Only present when the user interface was drawn since the last frame or the viewport
+        // changed. Or as a stopgap workaround we can also just always redraw.
+        if !(self.needs_redraw || viewport_changed || self.settings.always_redraw)
+            || physical_size.width == 0
+            || physical_size.height == 0
+        {
+            let _ = self.shared.skipped_frames.fetch_add(1, Ordering::Relaxed);
+
+            return;
+        }
+
+        self.debug.render_started();
+
+        if viewport_changed {
+            let logical_size = self.state.logical_size();
+
+            self.ensure_user_interface();
+
+            self.debug.layout_started();
+            let layout_started = Instant::now();
+            if let Some(user_interface) = self.user_interface.take() {
+                self.user_interface =
+                    Some(user_interface.relayout(logical_size, &mut self.renderer));
             }
-            RuntimeEvent::UserEvent(message) => {
-                run_action::<A, C>(
-                    message,
-                    &mut compositor,
-                    &mut renderer,
-                    &mut messages,
-                    &mut clipboard,
-                    &mut user_interface,
-                    &mut debug,
-                    &mut window_queue,
-                );
+            self.metrics.layout += layout_started.elapsed();
+            self.debug.layout_finished();
+
+            self.draw();
+
+            self.compositor.configure_surface(
+                &mut self.surface,
+                physical_size.width,
+                physical_size.height,
+            );
+
+            self.viewport_version = current_viewport_version;
+            self.damage.reset();
+            self.publish_size();
+        }
+
+        if self.settings.measure_damage {
+            self.metrics.damaged_pixels = self.damage.measure(
+                &mut self.renderer,
+                self.state.viewport(),
+                self.state.background_color(),
+            );
+        }
+
+        let overlay = self.overlay.lines(&self.debug, self.metrics.damaged_pixels);
+
+        let present_started = Instant::now();
+
+        let result = self.compositor.present(
+            &mut self.renderer,
+            &mut self.surface,
+            self.state.viewport(),
+            self.state.background_color(),
+            &overlay,
+        );
+
+        self.metrics.present += present_started.elapsed();
+
+        match result {
+            Ok(()) => {
+                self.debug.render_finished();
+
+                self.needs_redraw = false;
+                let _ = self.shared.presented_frames.fetch_add(1, Ordering::Relaxed);
             }
-            RuntimeEvent::RedrawRequested => {
-                #[cfg(feature = "trace")]
-                let _ = info_span!("Application", "FRAME").entered();
-
-                // Set whenever a baseview event or message gets handled. Or as a stopgap workaround
-                // we can also just always redraw.
-                if !(redraw_requested || settings.always_redraw) {
-                    continue;
+            Err(error) => match error {
+                // This is an unrecoverable error.
+                compositor::SurfaceError::OutOfMemory => {
+                    panic!("{error:?}");
                 }
+                _ => {
+                    self.debug.render_finished();
 
-                let physical_size = state.physical_size();
-
-                if physical_size.width == 0 || physical_size.height == 0 {
-                    continue;
+                    // Try again once the surface has recovered.
+                    self.needs_redraw = true;
+                    self.damage.reset();
                 }
+            },
+        }
 
-                debug.render_started();
-                let current_viewport_version = state.viewport_version();
-
-                if viewport_version != current_viewport_version {
-                    let logical_size = state.logical_size();
-
-                    debug.layout_started();
-                    user_interface = ManuallyDrop::new(
-                        ManuallyDrop::into_inner(user_interface)
-                            .relayout(logical_size, &mut renderer),
-                    );
-                    debug.layout_finished();
-
-                    debug.draw_started();
-                    let new_mouse_interaction = user_interface.draw(
-                        &mut renderer,
-                        state.theme(),
-                        &renderer::Style {
-                            text_color: state.text_color(),
-                        },
-                        state.cursor(),
-                    );
-
-                    if new_mouse_interaction != mouse_interaction {
-                        // TODO: Set mouse cursor for MacOS once baseview supports it.
-                        #[cfg(not(target_os = "macos"))]
-                        if let Err(_) = window_queue.set_mouse_cursor(
-                            crate::conversion::convert_mouse_interaction(new_mouse_interaction),
-                        ) {
-                            debug.log_message(
-                                &"could not send set_mouse_cursor command".to_string(),
-                            );
-                        }
-
-                        mouse_interaction = new_mouse_interaction;
-                    }
+        self.finish_frame();
+    }
 
-                    debug.draw_finished();
+    /// Parses a message sent through the automation socket.
+    #[cfg(all(unix, feature = "automation"))]
+    pub fn automation_message(&self, text: &str) -> Option<A::Message> {
+        self.application.automation_message(text)
+    }
 
-                    compositor.configure_surface(
-                        &mut surface,
-                        physical_size.width,
-                        physical_size.height,
-                    );
+    /// Returns the current [`Viewport`](crate::Viewport) of the window.
+    pub fn viewport(&self) -> &crate::Viewport {
+        self.state.viewport()
+    }
 
-                    viewport_version = current_viewport_version;
-                }
+    /// Renders the current user interface offscreen and returns its RGBA
+    /// pixels.
+    #[cfg(all(unix, feature = "automation"))]
+    pub fn screenshot(&mut self) -> Vec<u8> {
+        self.ensure_user_interface();
+        self.draw();
+
+        self.compositor.screenshot(
+            &mut self.renderer,
+            &mut self.surface,
+            self.state.viewport(),
+            self.state.background_color(),
+            &self.overlay.lines(&self.debug, None),
+        )
+    }
 
-                match compositor.present(
-                    &mut renderer,
-                    &mut surface,
-                    state.viewport(),
-                    state.background_color(),
-                    &debug.overlay(),
-                ) {
-                    Ok(()) => {
-                        debug.render_finished();
-
-                        // TODO: Handle animations!
-                        // Maybe we can use `ControlFlow::WaitUntil` for this.
+    /// Shows or hides the debug overlay.
+    pub fn toggle_debug(&mut self) {
+        if self.overlay.toggle(&mut self.debug) {
+            if self.settings.debug.layout_bounds {
+                self.invalidate_user_interface();
+            }
+
+            // Make sure the overlay is presented
+            self.did_process_event = true;
+        }
+    }
+
+    /// Lets the application know that the window is about to close.
+    pub fn will_close(&mut self) {
+        #[cfg(feature = "trace")]
+        Profiler::flush();
+
+        if let Some(message) = &self.window_subs.on_window_will_close {
+            // Send message to user before the window closes.
+            if let Some(message) = message() {
+                self.messages.push(message);
+            }
+
+            self.update_application();
+        }
+
+        // Dropping the widget tree lets widgets clean up right away, like
+        // parameter controls ending the gesture of an unfinished drag.
+        self.user_interface = None;
+        self.cache = Some(user_interface::Cache::default());
+    }
+
+    /// Stops drawing until [`resume`](Self::resume) is called, and lets the
+    /// application know.
+    ///
+    /// Held mouse buttons are released first, like when the window loses
+    /// focus, so widgets don't stay stuck in a drag.
+    pub fn suspend(&mut self) {
+        if self.suspended {
+            return;
+        }
+
+        let first_event = self.events.len();
+        self.state.release_buttons(&mut self.events);
+
+        if self.events.len() > first_event {
+            let _ = self.process_events(first_event);
+        }
+
+        self.suspended = true;
+
+        if let Some(message) = &self.window_subs.on_suspend {
+            if let Some(message) = message() {
+                self.messages.push(message);
+            }
+        }
+
+        // Handle the message, and the ones the released buttons produced,
+        // before the host hides the window.
+        if !self.messages.is_empty() {
+            self.update_application();
+        }
+
+        if self.settings.pause_subscriptions_while_suspended {
+            self.runtime.track(Vec::new());
+        }
+    }
+
+    /// Resumes drawing after [`suspend`](Self::suspend), starting with a full
+    /// redraw of the window.
+    pub fn resume(&mut self) {
+        if !self.suspended {
+            return;
+        }
+
+        self.suspended = false;
+
+        if let Some(message) = &self.window_subs.on_resume {
+            if let Some(message) = message() {
+                self.messages.push(message);
+            }
+        }
+
+        for operation in std::mem::take(&mut self.pending_operations) {
+            self.handle_action(Action::Widget(operation));
+        }
+
+        // Updating also subscribes again to paused subscriptions.
+        self.needs_update = true;
+        self.did_process_event = true;
+        self.damage.reset();
+    }
+
+    /// Returns the scale factor the `baseview` window uses to convert between
+    /// logical and physical sizes.
+    pub fn window_scale_factor(&self) -> f64 {
+        self.state.window_scale_factor()
+    }
+
+    /// Applies the scale factor set by the host, and notifies the application.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.state.set_scale_factor(scale_factor);
+
+        if let Some(message) = &self.window_subs.on_scale_factor_changed {
+            if let Some(message) = message(scale_factor) {
+                self.messages.push(message);
+            }
+        }
+    }
+
+    /// Returns the logical size the window takes when `requested` is asked for.
+    pub fn constrain_size(&self, requested: Size) -> Size {
+        self.application.constrain_size(requested)
+    }
+
+    /// Sets the physical size of the viewport right away, instead of waiting
+    /// for the window to report it. The user interface is laid out again on the
+    /// next redraw.
+    pub fn resize(&mut self, physical_size: Size<u32>) {
+        self.state.resize(physical_size);
+    }
+
+    /// Takes the commands for the `baseview` window queued since the last
+    /// call.
+    pub fn take_window_commands(&mut self) -> Vec<WindowCommand> {
+        self.window_queue.take()
+    }
+
+    /// Takes the values emitted with [`window::emit`] since the last call.
+    ///
+    /// [`window::emit`]: crate::window::emit
+    pub fn take_outputs(&mut self) -> Vec<Box<dyn Any + Send>> {
+        self.window_queue.take_outputs()
+    }
+
+    /// Runs the queued events through the user interface, and returns whether
+    /// any of the events starting at `first_event` were captured.
+    fn process_events(&mut self, first_event: usize) -> bool {
+        if self.events.is_empty() {
+            return false;
+        }
+
+        self.ensure_user_interface();
+
+        let Some(user_interface) = self.user_interface.as_mut() else {
+            return false;
+        };
+
+        self.debug.event_processing_started();
+
+        let (interface_state, statuses) = user_interface.update(
+            &self.events,
+            self.state.cursor(),
+            &mut self.renderer,
+            &mut self.clipboard,
+            &mut self.messages,
+        );
+
+        match interface_state {
+            user_interface::State::Outdated => {
+                self.needs_update = true;
+            }
+            user_interface::State::Updated {
+                redraw_request: Some(redraw_request),
+            } => {
+                let redraw_at = match redraw_request {
+                    window::RedrawRequest::NextFrame => Instant::now(),
+                    window::RedrawRequest::At(at) => at,
+                };
+
+                self.redraw_at = Some(
+                    self.redraw_at
+                        .map_or(redraw_at, |current| current.min(redraw_at)),
+                );
+            }
+            user_interface::State::Updated {
+                redraw_request: None,
+            } => {}
+        }
+
+        self.debug.event_processing_finished();
+
+        let mut captured = false;
+        let mut focus_changed = false;
+
+        self.metrics.events += self.events.len();
+
+        for event in &self.events {
+            self.overlay.log_event(event);
+        }
+
+        for (i, (event, status)) in self.events.drain(..).zip(statuses).enumerate() {
+            let mut status = status;
+
+            if self.settings.focus_traversal && status == event::Status::Ignored {
+                if let Some(backwards) = focus_traversal(&event) {
+                    // Tab is only taken from the host when it actually moved
+                    // the focus.
+                    if move_focus(user_interface, &self.renderer, backwards) {
+                        focus_changed = true;
+                        status = event::Status::Captured;
                     }
-                    Err(error) => match error {
-                        // This is an unrecoverable error.
-                        compositor::SurfaceError::OutOfMemory => {
-                            panic!("{error:?}");
-                        }
-                        _ => {
-                            debug.render_finished();
-
-                            redraw_requested = true;
-                        }
-                    },
                 }
             }
-            RuntimeEvent::Baseview((event, do_send_status)) => {
-                state.update(&event, &mut debug);
 
-                let ignore_non_modifier_keys = application
-                    .ignore_non_modifier_keys()
-                    .unwrap_or(settings.ignore_non_modifier_keys);
+            captured |= i >= first_event && status == event::Status::Captured;
 
-                crate::conversion::baseview_to_iced_events(
+            self.runtime
+                .broadcast(crate::futures::subscription::Event::Interaction {
+                    window: self.window_id,
                     event,
-                    &mut events,
-                    state.modifiers_mut(),
-                    ignore_non_modifier_keys,
+                    status,
+                });
+        }
+
+        if focus_changed {
+            if let Some(message) = &self.window_subs.on_focus_changed {
+                let focused = run_operation(
+                    user_interface,
+                    &self.renderer,
+                    Box::new(operation::focusable::find_focused()),
                 );
 
-                if events.is_empty() {
-                    if do_send_status {
-                        *event_status.borrow_mut() = EventStatus::Ignored;
-                    }
-                    continue;
+                if let Some(message) = message(focused) {
+                    self.messages.push(message);
                 }
+            }
+
+            self.needs_update = true;
+        }
+
+        captured
+    }
+
+    /// Compares the [`WindowSubs::params`] with the values seen on the last
+    /// frame, and makes sure the frame is drawn if any of them changed.
+    fn check_params(&mut self) {
+        let Some(params) = &self.window_subs.params else {
+            self.params_seen.clear();
+
+            return;
+        };
+
+        if self.params_seen.len() != params.len() {
+            // Newly registered values are reported as changed.
+            self.params_seen = vec![None; params.len()];
+        }
+
+        let changed: Vec<usize> = params
+            .iter()
+            .zip(&mut self.params_seen)
+            .enumerate()
+            .filter_map(|(i, (param, seen))| {
+                let value = param.load(Ordering::Relaxed).to_bits();
+
+                (seen.replace(value) != Some(value)).then_some(i)
+            })
+            .collect();
+
+        if changed.is_empty() {
+            return;
+        }
 
-                did_process_event = true;
+        self.did_process_event = true;
+
+        if let Some(message) = &self.window_subs.on_params_changed {
+            if let Some(message) = message(changed) {
+                self.messages.push(message);
             }
-            RuntimeEvent::WillClose => {
-                if let Some(message) = &window_subs.on_window_will_close {
-                    // Send message to user before exiting the loop.
+        }
+    }
 
-                    if let Some(message) = message() {
-                        messages.push(message);
-                    }
-                    let cache = ManuallyDrop::into_inner(user_interface).into_cache();
-
-                    update(
-                        &mut application,
-                        &mut runtime,
-                        &mut debug,
-                        &mut messages,
-                        &mut window_subs,
-                    );
-
-                    // Update window
-                    state.synchronize(&application);
-
-                    user_interface = ManuallyDrop::new(build_user_interface(
-                        &mut application,
-                        cache,
-                        &mut renderer,
-                        state.logical_size(),
-                        &mut debug,
-                    ));
-                }
+    /// Makes the size limits of the application available to the
+    /// `WindowHandle`.
+    fn publish_size_limits(&self) {
+        *self
+            .shared
+            .size_limits
+            .lock()
+            .unwrap_or_else(|error| error.into_inner()) = SizeLimits::of(self.application.as_ref());
+    }
+
+    /// Makes the viewport available to the `WindowHandle`.
+    fn publish_size(&self) {
+        *self
+            .shared
+            .viewport
+            .lock()
+            .unwrap_or_else(|error| error.into_inner()) = Some(self.state.viewport().clone());
+    }
+
+    /// Publishes the timings of the frame that was just drawn, and warns when it
+    /// exceeded the frame budget.
+    fn finish_frame(&mut self) {
+        let metrics = std::mem::take(&mut self.metrics);
+
+        if let Some(budget) = self.settings.frame_budget {
+            let total = metrics.total();
+
+            if total > budget {
+                let (phase, duration) = metrics.slowest_phase();
 
-                break;
+                log::warn!(
+                    "Frame took {total:?}, exceeding the budget of {budget:?}. \
+                    The slowest phase was {phase} with {duration:?}."
+                );
             }
         }
+
+        *self
+            .shared
+            .frame_metrics
+            .lock()
+            .unwrap_or_else(|error| error.into_inner()) = metrics;
+    }
+
+    /// Lets the application handle the queued messages that don't need the user
+    /// interface to be rebuilt. See [`Application::update_in_place`].
+    fn update_in_place(&mut self) {
+        let application = self.application.as_ref();
+        let update_started = Instant::now();
+
+        self.messages = std::mem::take(&mut self.messages)
+            .into_iter()
+            .filter_map(|message| application.update_in_place(message))
+            .collect();
+
+        self.metrics.update += update_started.elapsed();
     }
 
-    // Manually drop the user interface
-    let _ = ManuallyDrop::into_inner(user_interface);
+    /// Feeds the queued messages to the application and synchronizes the
+    /// [`State`] with it. The user interface is rebuilt when it's needed next.
+    fn update_application(&mut self) {
+        self.invalidate_user_interface();
+
+        let update_started = Instant::now();
+        let mut actions = Vec::new();
+
+        update(
+            self.application.as_mut(),
+            &mut self.runtime,
+            &mut self.debug,
+            &mut self.messages,
+            &mut self.window_subs,
+            &mut actions,
+        );
+
+        self.metrics.update += update_started.elapsed();
+
+        for action in actions {
+            self.handle_action(action);
+        }
+
+        // Update window
+        self.state.synchronize(&self.application);
+        self.publish_size_limits();
+    }
+
+    /// Draws the user interface and updates the mouse cursor.
+    fn draw(&mut self) {
+        self.ensure_user_interface();
+
+        let Some(user_interface) = self.user_interface.as_mut() else {
+            return;
+        };
+
+        #[cfg(all(feature = "tiny-skia", feature = "image"))]
+        crate::widget::set_scale_factor(self.state.viewport().scale_factor());
+
+        self.debug.draw_started();
+        let draw_started = Instant::now();
+        let new_mouse_interaction = user_interface.draw(
+            &mut self.renderer,
+            self.state.theme(),
+            &renderer::Style {
+                text_color: self.state.text_color(),
+            },
+            self.state.cursor(),
+        );
+        self.metrics.draw += draw_started.elapsed();
+        self.debug.draw_finished();
+
+        if new_mouse_interaction != self.mouse_interaction {
+            // TODO: Set mouse cursor for MacOS once baseview supports it.
+            #[cfg(not(target_os = "macos"))]
+            self.window_queue
+                .set_mouse_cursor(crate::conversion::convert_mouse_interaction(
+                    new_mouse_interaction,
+                ));
+
+            self.mouse_interaction = new_mouse_interaction;
+        }
+    }
+
+    /// Builds the user interface if it was invalidated.
+    fn ensure_user_interface(&mut self) {
+        if self.user_interface.is_some() {
+            return;
+        }
+
+        let cache = self.cache.take().unwrap_or_default();
+
+        // SAFETY: The user interface only borrows the boxed application, whose address never
+        // changes. The user interface is always invalidated before the application gets mutated,
+        // and it is dropped before the application.
+        let application: &'static A = unsafe { &*(self.application.as_ref() as *const A) };
+
+        self.user_interface = Some(build_user_interface(
+            application,
+            cache,
+            &mut self.renderer,
+            self.state.logical_size(),
+            &mut self.debug,
+            &mut self.metrics,
+            self.overlay.layout_bounds(),
+        ));
+    }
+
+    /// Drops the user interface, keeping its cache around until it's rebuilt.
+    fn invalidate_user_interface(&mut self) {
+        if let Some(user_interface) = self.user_interface.take() {
+            self.cache = Some(user_interface.into_cache());
+        }
+    }
 }
 
 /// Builds a [`UserInterface`] for the provided [`Application`], logging
-/// [`struct@Debug`] information accordingly.
+/// [`struct@Debug`] information and adding the time spent to the
+/// [`FrameMetrics`] accordingly. The layout bounds of every widget are outlined
+/// with the `explain` color, if any.
 pub fn build_user_interface<'a, A: Application>(
     application: &'a A,
     cache: user_interface::Cache,
     renderer: &mut Renderer,
     size: Size,
     debug: &mut Debug,
+    metrics: &mut FrameMetrics,
+    explain: Option<Color>,
 ) -> UserInterface<'a, A::Message, A::Theme, Renderer>
 where
     A::Theme: DefaultStyle,
@@ -646,7 +1217,14 @@ where
     let view_span = info_span!("Application", "VIEW").entered();
 
     debug.view_started();
+    let view_started = Instant::now();
     let view = application.view();
+    metrics.view += view_started.elapsed();
+
+    let view = match explain {
+        Some(color) => view.explain(color),
+        None => view,
+    };
 
     #[cfg(feature = "trace")]
     let _ = view_span.exit();
@@ -656,7 +1234,9 @@ where
     let layout_span = info_span!("Application", "LAYOUT").entered();
 
     debug.layout_started();
+    let layout_started = Instant::now();
     let user_interface = UserInterface::build(view, size, cache, renderer);
+    metrics.layout += layout_started.elapsed();
 
     #[cfg(feature = "trace")]
     let _ = layout_span.exit();
@@ -667,13 +1247,16 @@ where
 
 /// Updates an [`Application`] by feeding it the provided messages, spawning any
 /// resulting [`Command`], and tracking its [`Subscription`].
+///
+/// The actions the commands have ready right away are added to `actions`, see
+/// [`spawn`].
 pub fn update<A: Application, E: Executor>(
     application: &mut A,
     runtime: &mut Runtime<E, Proxy<A::Message>, iced_runtime::Action<A::Message>>,
     debug: &mut Debug,
     messages: &mut Vec<A::Message>,
     window_subs: &mut WindowSubs<A::Message>,
-    //window_queue: &mut WindowQueue,
+    actions: &mut Vec<Action<A::Message>>,
 ) where
     A::Theme: DefaultStyle,
 {
@@ -690,9 +1273,7 @@ pub fn update<A: Application, E: Executor>(
         let _ = update_span.exit();
         debug.update_finished();
 
-        if let Some(stream) = crate::runtime::task::into_stream(task) {
-            runtime.run(stream);
-        }
+        spawn(runtime, task, actions);
     }
 
     let subscription = runtime.enter(|| application.subscription(window_subs));
@@ -701,15 +1282,51 @@ pub fn update<A: Application, E: Executor>(
     ));
 }
 
-/// Runs the actions of a [`Command`].
+/// Runs a [`Task`] on the runtime, except for the actions it has ready right
+/// away, which are added to `actions` to be handled on the window thread.
+///
+/// This keeps the commands of [`window::command`] and the values of
+/// [`window::emit`] in order with the update that returned them, and delivers
+/// them even when the window closes and won't handle the actions sent by the
+/// runtime anymore.
+///
+/// [`window::command`]: crate::window::command
+/// [`window::emit`]: crate::window::emit
+pub fn spawn<M: Send, E: Executor>(
+    runtime: &mut Runtime<E, Proxy<M>, Action<M>>,
+    task: Task<M>,
+    actions: &mut Vec<Action<M>>,
+) {
+    use futures::StreamExt;
+    use std::task::{Context, Poll};
+
+    let Some(mut stream) = crate::runtime::task::into_stream(task) else {
+        return;
+    };
+
+    let mut context = Context::from_waker(futures::task::noop_waker_ref());
+
+    loop {
+        match runtime.enter(|| stream.poll_next_unpin(&mut context)) {
+            Poll::Ready(Some(action)) => actions.push(action),
+            Poll::Ready(None) => return,
+            // The runtime polls the stream again, with a waker of its own.
+            Poll::Pending => {
+                runtime.run(stream);
+
+                return;
+            }
+        }
+    }
+}
+
+/// Runs the actions of a [`Command`] that concern the window and the system.
+///
+/// Messages and widget operations are handled by the [`Instance`] itself.
 pub fn run_action<A, C>(
     action: Action<A::Message>,
     compositor: &mut C,
-    renderer: &Renderer,
-    messages: &mut Vec<A::Message>,
     clipboard: &mut Clipboard,
-    interface: &mut UserInterface<'_, A::Message, A::Theme, Renderer>,
-    debug: &mut Debug,
     window_queue: &mut WindowQueue,
 ) where
     C: Compositor<Renderer = Renderer> + 'static,
@@ -719,9 +1336,7 @@ pub fn run_action<A, C>(
     use iced_runtime::window::Action as IWindowAction;
 
     match action {
-        Action::Output(message) => {
-            messages.push(message);
-        }
+        Action::Output(_) | Action::Widget(_) => {}
         Action::Clipboard(action) => match action {
             clipboard::Action::Read { target, channel } => {
                 let _ = channel.send(clipboard.read(target));
@@ -732,19 +1347,13 @@ pub fn run_action<A, C>(
         },
         Action::Window(action) => match action {
             IWindowAction::Close(_) => {
-                if let Err(_) = window_queue.close_window() {
-                    debug.log_message(&"could not send close_window command".to_string());
-                }
+                window_queue.close_window();
             }
             IWindowAction::Resize(_, size) => {
-                if let Err(_) = window_queue.resize_window(size) {
-                    debug.log_message(&"could not send resize_window command".to_string());
-                }
+                window_queue.resize_window(size);
             }
             IWindowAction::GainFocus(_) => {
-                if let Err(_) = window_queue.focus() {
-                    debug.log_message(&"could not send get_window command".to_string());
-                }
+                window_queue.focus();
             }
             _ => {}
         },
@@ -762,21 +1371,6 @@ pub fn run_action<A, C>(
                 }
             }
         },
-        Action::Widget(operation) => {
-            let mut current_operation = Some(operation);
-
-            while let Some(mut operation) = current_operation.take() {
-                interface.operate(renderer, operation.as_mut());
-
-                match operation.finish() {
-                    operation::Outcome::None => {}
-                    operation::Outcome::Some(()) => {}
-                    operation::Outcome::Chain(next) => {
-                        current_operation = Some(next);
-                    }
-                }
-            }
-        }
         Action::LoadFont { bytes, channel } => {
             // TODO: Error handling (?)
             compositor.load_font(bytes.clone());
@@ -784,9 +1378,233 @@ pub fn run_action<A, C>(
             let _ = channel.send(Ok(()));
         }
         Action::Exit => {
-            if let Err(_) = window_queue.close_window() {
-                debug.log_message(&"could not send exit command".to_string());
+            window_queue.close_window();
+        }
+    }
+}
+
+/// Runs a widget [`Operation`] on the [`UserInterface`] until it finishes,
+/// following any chained operations, and returns its output.
+///
+/// [`Operation`]: operation::Operation
+pub fn run_operation<Message, Theme, T>(
+    interface: &mut UserInterface<'_, Message, Theme, Renderer>,
+    renderer: &Renderer,
+    operation: Box<dyn operation::Operation<T>>,
+) -> Option<T> {
+    let mut current_operation = Some(operation);
+
+    while let Some(mut operation) = current_operation.take() {
+        interface.operate(renderer, &mut operation::black_box(operation.as_mut()));
+
+        match operation.finish() {
+            operation::Outcome::None => {}
+            operation::Outcome::Some(output) => return Some(output),
+            operation::Outcome::Chain(next) => {
+                current_operation = Some(next);
             }
         }
     }
+
+    None
+}
+
+/// Merges the cursor movements and wheel scrolls in `events[start..]` into
+/// the event right before them when that is of the same kind.
+fn coalesce_events(events: &mut Vec<crate::core::Event>, start: usize) {
+    use crate::core::mouse::ScrollDelta;
+    use crate::core::Event;
+
+    let mut i = start.max(1);
+
+    while i < events.len() {
+        let merged = match (&events[i - 1], &events[i]) {
+            (
+                Event::Mouse(mouse::Event::CursorMoved { .. }),
+                Event::Mouse(mouse::Event::CursorMoved { position }),
+            ) => Some(mouse::Event::CursorMoved {
+                position: *position,
+            }),
+            (
+                Event::Mouse(mouse::Event::WheelScrolled {
+                    delta: ScrollDelta::Lines { x: x1, y: y1 },
+                }),
+                Event::Mouse(mouse::Event::WheelScrolled {
+                    delta: ScrollDelta::Lines { x: x2, y: y2 },
+                }),
+            ) => Some(mouse::Event::WheelScrolled {
+                delta: ScrollDelta::Lines {
+                    x: x1 + x2,
+                    y: y1 + y2,
+                },
+            }),
+            (
+                Event::Mouse(mouse::Event::WheelScrolled {
+                    delta: ScrollDelta::Pixels { x: x1, y: y1 },
+                }),
+                Event::Mouse(mouse::Event::WheelScrolled {
+                    delta: ScrollDelta::Pixels { x: x2, y: y2 },
+                }),
+            ) => Some(mouse::Event::WheelScrolled {
+                delta: ScrollDelta::Pixels {
+                    x: x1 + x2,
+                    y: y1 + y2,
+                },
+            }),
+            _ => None,
+        };
+
+        if let Some(merged) = merged {
+            events[i - 1] = Event::Mouse(merged);
+            let _ = events.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Moves the keyboard focus to the next or previous focusable widget, and
+/// returns whether the focus changed.
+fn move_focus<Message, Theme>(
+    interface: &mut UserInterface<'_, Message, Theme, Renderer>,
+    renderer: &Renderer,
+    backwards: bool,
+) -> bool {
+    let focused = |interface: &mut UserInterface<'_, Message, Theme, Renderer>| {
+        run_operation(interface, renderer, Box::new(operation::focusable::count()))
+            .and_then(|count| count.focused)
+    };
+
+    let before = focused(interface);
+
+    let operation: Box<dyn operation::Operation> = if backwards {
+        Box::new(operation::focusable::focus_previous())
+    } else {
+        Box::new(operation::focusable::focus_next())
+    };
+
+    let _ = run_operation(interface, renderer, operation);
+
+    focused(interface) != before
+}
+
+/// Returns whether the event should move the keyboard focus, and if so,
+/// whether it should move backwards.
+fn focus_traversal(event: &crate::core::Event) -> Option<bool> {
+    use crate::core::keyboard::{self, key};
+
+    match event {
+        crate::core::Event::Keyboard(keyboard::Event::KeyPressed {
+            key: keyboard::Key::Named(key::Named::Tab),
+            modifiers,
+            ..
+        }) if !(modifiers.control() || modifiers.alt() || modifiers.logo()) => {
+            Some(modifiers.shift())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{coalesce_events, spawn};
+    use crate::core::mouse::{self, ScrollDelta};
+    use crate::core::{Event, Point};
+
+    fn moved(x: f32) -> Event {
+        Event::Mouse(mouse::Event::CursorMoved {
+            position: Point::new(x, 0.0),
+        })
+    }
+
+    fn scrolled(delta: ScrollDelta) -> Event {
+        Event::Mouse(mouse::Event::WheelScrolled { delta })
+    }
+
+    #[test]
+    fn cursor_movements_keep_the_last_position() {
+        let mut events = vec![moved(1.0)];
+
+        events.push(moved(2.0));
+        coalesce_events(&mut events, 1);
+        events.push(moved(3.0));
+        coalesce_events(&mut events, 1);
+
+        assert_eq!(events, vec![moved(3.0)]);
+    }
+
+    #[test]
+    fn wheel_scrolls_of_the_same_unit_add_up() {
+        let mut events = vec![scrolled(ScrollDelta::Lines { x: 0.0, y: 1.0 })];
+
+        events.push(scrolled(ScrollDelta::Lines { x: 1.0, y: 2.0 }));
+        coalesce_events(&mut events, 1);
+
+        assert_eq!(
+            events,
+            vec![scrolled(ScrollDelta::Lines { x: 1.0, y: 3.0 })]
+        );
+
+        events.push(scrolled(ScrollDelta::Pixels { x: 0.0, y: 5.0 }));
+        coalesce_events(&mut events, 1);
+
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn other_events_are_kept_in_order() {
+        let pressed = Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left));
+        let mut events = vec![moved(1.0), pressed.clone()];
+
+        events.push(moved(2.0));
+        coalesce_events(&mut events, 2);
+
+        assert_eq!(events, vec![moved(1.0), pressed, moved(2.0)]);
+    }
+
+    #[test]
+    fn an_empty_queue_is_left_alone() {
+        let mut events = Vec::new();
+
+        coalesce_events(&mut events, 0);
+        events.push(moved(1.0));
+        coalesce_events(&mut events, 0);
+
+        assert_eq!(events, vec![moved(1.0)]);
+    }
+
+    #[test]
+    fn spawn_takes_the_actions_that_are_ready() {
+        use crate::futures::futures::{channel::mpsc, future};
+        use crate::futures::{backend::null, Executor, Runtime};
+        use iced_runtime::{Action, Task};
+
+        let (sender, _receiver) = mpsc::unbounded();
+        let mut runtime = Runtime::new(
+            null::Executor::new().expect("create executor"),
+            crate::Proxy::new(sender),
+        );
+        let mut actions = Vec::new();
+
+        spawn(
+            &mut runtime,
+            Task::done(1).chain(Task::done(2)),
+            &mut actions,
+        );
+        spawn(
+            &mut runtime,
+            Task::perform(future::pending::<()>(), |()| 3),
+            &mut actions,
+        );
+
+        let messages: Vec<i32> = actions
+            .into_iter()
+            .map(|action| match action {
+                Action::Output(message) => message,
+                _ => panic!("expected a message"),
+            })
+            .collect();
+
+        assert_eq!(messages, vec![1, 2]);
+    }
 }
@@ -0,0 +1,151 @@
+//! Measure the regions of the window that changed between frames.
+use crate::core::Color;
+use crate::graphics::Viewport;
+use crate::Renderer;
+
+#[cfg(feature = "tiny-skia")]
+use crate::core::Rectangle;
+#[cfg(feature = "tiny-skia")]
+use crate::graphics::damage;
+
+/// Diffs the layers drawn by the software renderer against the ones of the
+/// last presented frame, to report how much of the window was damaged.
+///
+/// The tiny-skia compositor computes the same damage when presenting and only
+/// redraws those regions of its buffer. It doesn't expose them, so this
+/// repeats the diff, which is why it only runs when
+/// [`IcedBaseviewSettings::measure_damage`] is set.
+///
+/// [`IcedBaseviewSettings::measure_damage`]: crate::settings::IcedBaseviewSettings::measure_damage
+#[derive(Debug, Default)]
+pub(crate) struct Damage {
+    #[cfg(feature = "tiny-skia")]
+    previous: Option<(Vec<iced_tiny_skia::Layer>, Color)>,
+}
+
+impl Damage {
+    /// Forgets the last presented frame, so the next one is damaged entirely.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Returns the number of physical pixels damaged by the current frame, and
+    /// remembers it as the last presented frame.
+    ///
+    /// Returns `None` when the damage is unknown, for example with the `wgpu`
+    /// renderer.
+    #[allow(unused_variables)]
+    pub fn measure(
+        &mut self,
+        renderer: &mut Renderer,
+        viewport: &Viewport,
+        background_color: Color,
+    ) -> Option<u64> {
+        #[cfg(feature = "tiny-skia")]
+        {
+            let layers = tiny_skia(renderer)?.layers();
+            let bounds = Rectangle::with_size(viewport.logical_size());
+
+            let regions = match &self.previous {
+                Some((previous, color)) if *color == background_color => damage::group(
+                    damage::diff(
+                        previous,
+                        layers,
+                        |layer| vec![layer.bounds],
+                        iced_tiny_skia::Layer::damage,
+                    ),
+                    bounds,
+                ),
+                _ => vec![bounds],
+            };
+
+            self.previous = Some((layers.to_vec(), background_color));
+
+            let scale_factor = viewport.scale_factor() as f32;
+
+            Some(
+                regions
+                    .iter()
+                    .map(|region| (region.area() * scale_factor * scale_factor).round() as u64)
+                    .sum(),
+            )
+        }
+
+        #[cfg(not(feature = "tiny-skia"))]
+        None
+    }
+}
+
+#[cfg(all(feature = "tiny-skia", feature = "wgpu"))]
+fn tiny_skia(renderer: &mut Renderer) -> Option<&mut iced_tiny_skia::Renderer> {
+    match renderer {
+        iced_renderer::fallback::Renderer::Primary(_) => None,
+        iced_renderer::fallback::Renderer::Secondary(renderer) => Some(renderer),
+    }
+}
+
+#[cfg(all(feature = "tiny-skia", not(feature = "wgpu")))]
+fn tiny_skia(renderer: &mut Renderer) -> Option<&mut iced_tiny_skia::Renderer> {
+    Some(renderer)
+}
+
+#[cfg(all(test, feature = "tiny-skia"))]
+mod tests {
+    use super::*;
+
+    use crate::core::renderer::{self, Renderer as _};
+    use crate::core::{Font, Pixels, Point, Size};
+
+    fn renderer() -> Renderer {
+        let renderer = iced_tiny_skia::Renderer::new(Font::default(), Pixels(16.0));
+
+        #[cfg(feature = "wgpu")]
+        let renderer = iced_renderer::fallback::Renderer::Secondary(renderer);
+
+        renderer
+    }
+
+    fn fill(renderer: &mut Renderer, bounds: Rectangle) {
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                ..renderer::Quad::default()
+            },
+            Color::BLACK,
+        );
+    }
+
+    #[test]
+    fn measures_only_the_changed_regions() {
+        let viewport = Viewport::with_physical_size(Size::new(200, 100), 2.0);
+        let square = Rectangle::new(Point::new(10.0, 10.0), Size::new(10.0, 10.0));
+        let mut renderer = renderer();
+        let mut damage = Damage::default();
+
+        fill(&mut renderer, square);
+        assert_eq!(
+            damage.measure(&mut renderer, &viewport, Color::WHITE),
+            Some(200 * 100)
+        );
+
+        renderer.clear();
+        fill(&mut renderer, square);
+        assert_eq!(
+            damage.measure(&mut renderer, &viewport, Color::WHITE),
+            Some(0)
+        );
+
+        // The square, with the pixel the renderer adds around it for
+        // antialiasing.
+        renderer.clear();
+        assert_eq!(
+            damage.measure(&mut renderer, &viewport, Color::WHITE),
+            Some(24 * 24)
+        );
+
+        assert_eq!(
+            damage.measure(&mut renderer, &viewport, Color::BLACK),
+            Some(200 * 100)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Extend the debug overlay of the `debug` feature.
+use std::collections::VecDeque;
+
+use crate::core::keyboard::Key;
+use crate::core::{Color, Event};
+use crate::runtime::Debug;
+use crate::settings::DebugSettings;
+
+/// The color used to outline the layout bounds of widgets.
+const LAYOUT_BOUNDS_COLOR: Color = Color::from_rgb(1.0, 0.0, 1.0);
+
+/// Keeps track of whether the debug overlay is shown and of the extra
+/// information it lists on top of [`Debug::overlay`].
+#[derive(Debug)]
+pub(crate) struct Overlay {
+    settings: DebugSettings,
+    is_enabled: bool,
+    events: VecDeque<String>,
+}
+
+impl Overlay {
+    pub fn new(settings: DebugSettings) -> Self {
+        Self {
+            settings,
+            is_enabled: false,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Shows or hides the overlay. Returns whether anything changed, which is
+    /// never the case without the `debug` feature.
+    pub fn toggle(&mut self, debug: &mut Debug) -> bool {
+        #[cfg(feature = "debug")]
+        {
+            debug.toggle();
+
+            self.is_enabled = !self.is_enabled;
+            self.events.clear();
+
+            true
+        }
+
+        #[cfg(not(feature = "debug"))]
+        {
+            let _ = debug;
+
+            false
+        }
+    }
+
+    /// Returns whether `event` is the configured shortcut to toggle the
+    /// overlay.
+    pub fn is_toggle_shortcut(&self, event: &baseview::Event) -> bool {
+        let Some(toggle_key) = self.settings.toggle_key else {
+            return false;
+        };
+
+        let baseview::Event::Keyboard(event) = event else {
+            return false;
+        };
+
+        event.state == keyboard_types::KeyState::Down
+            && !event.repeat
+            && crate::conversion::baseview_to_iced_modifiers(event.modifiers)
+                == self.settings.toggle_modifiers
+            && crate::conversion::baseview_to_iced_key(event.key.clone()) == Key::Named(toggle_key)
+    }
+
+    /// Returns the color to outline the layout bounds of widgets with, if
+    /// they should be outlined.
+    pub fn layout_bounds(&self) -> Option<Color> {
+        (self.is_enabled && self.settings.layout_bounds).then_some(LAYOUT_BOUNDS_COLOR)
+    }
+
+    /// Adds an event processed by the user interface to the event log.
+    pub fn log_event(&mut self, event: &Event) {
+        if !self.is_enabled || self.settings.event_log_size == 0 {
+            return;
+        }
+
+        if self.events.len() >= self.settings.event_log_size {
+            let _ = self.events.pop_front();
+        }
+
+        self.events.push_back(format!("{event:?}"));
+    }
+
+    /// Returns the lines of text to show on the overlay, including the number
+    /// of damaged pixels when it was measured.
+    pub fn lines(&self, debug: &Debug, damaged_pixels: Option<u64>) -> Vec<String> {
+        let mut lines = debug.overlay();
+
+        if let Some(damaged_pixels) = damaged_pixels.filter(|_| self.is_enabled) {
+            lines.push(format!("Damaged pixels: {damaged_pixels}"));
+        }
+
+        if self.is_enabled && self.settings.event_log_size > 0 {
+            lines.push(String::from("Last events:"));
+            lines.extend(self.events.iter().map(|event| {
+                if event.len() <= 100 {
+                    format!("    {event}")
+                } else {
+                    format!("    {event:.100}...")
+                }
+            }));
+        }
+
+        lines
+    }
+}
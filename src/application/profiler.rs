@@ -1,40 +1,71 @@
 //! A simple profiler for Iced.
-use std::ffi::OsStr;
 use std::path::Path;
-use std::time::Duration;
+use std::sync::OnceLock;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::Registry;
 #[cfg(feature = "chrome-trace")]
 use {
+    std::ffi::OsStr,
+    std::sync::Mutex,
+    std::time::Duration,
     tracing_chrome::FlushGuard,
     tracing_subscriber::fmt::{format::DefaultFields, FormattedFields},
 };
 
+/// The profiler of the process. Plugin hosts often open several instances of the same plugin,
+/// but the global subscriber can only be set once.
+static PROFILER: OnceLock<Profiler> = OnceLock::new();
+
 /// Profiler state. This will likely need to be updated or reworked when adding new tracing backends.
 #[allow(missing_debug_implementations)]
 pub struct Profiler {
     #[cfg(feature = "chrome-trace")]
-    /// [`FlushGuard`] must not be dropped until the application scope is dropped for accurate tracing.
-    _guard: FlushGuard,
+    /// The profiler lives until the process exits, so the trace is written to disk by flushing
+    /// this [`FlushGuard`] instead of dropping it.
+    guard: Mutex<FlushGuard>,
 }
 
 impl Profiler {
-    /// Initializes the [`Profiler`].
-    pub fn init() -> Self {
+    /// Initializes the [`Profiler`] of the process. Only the first call does anything, later ones
+    /// return the existing profiler.
+    ///
+    /// With the `chrome-trace` feature the trace is written to `output`. When it's `None`, the
+    /// `CHROME_TRACE_FILE` environment variable is used, or else a file in a `traces` directory
+    /// next to the executable.
+    pub fn init(output: Option<&Path>) -> &'static Self {
+        PROFILER.get_or_init(|| Self::new(output))
+    }
+
+    /// Writes the trace recorded so far to disk.
+    pub fn flush() {
+        #[cfg(feature = "chrome-trace")]
+        if let Some(profiler) = PROFILER.get() {
+            profiler
+                .guard
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .flush();
+        }
+    }
+
+    #[allow(unused_variables)]
+    fn new(output: Option<&Path>) -> Self {
         // Registry stores the spans & generates unique span IDs
         let subscriber = Registry::default();
 
-        let default_path = Path::new(env!("CARGO_MANIFEST_DIR"));
-        let curr_exe = std::env::current_exe().unwrap_or_else(|_| default_path.to_path_buf());
-        let out_dir = curr_exe.parent().unwrap_or(default_path).join("traces");
-
         #[cfg(feature = "chrome-trace")]
         let (chrome_layer, guard) = {
+            let default_path = Path::new(env!("CARGO_MANIFEST_DIR"));
+            let curr_exe = std::env::current_exe().unwrap_or_else(|_| default_path.to_path_buf());
+            let out_dir = curr_exe.parent().unwrap_or(default_path).join("traces");
+
             let mut layer = tracing_chrome::ChromeLayerBuilder::new();
 
             // Optional configurable env var: CHROME_TRACE_FILE=/path/to/trace_file/file.json,
             // for uploading to chrome://tracing (old) or ui.perfetto.dev (new).
-            if let Ok(path) = std::env::var("CHROME_TRACE_FILE") {
+            if let Some(path) = output {
+                layer = layer.file(path);
+            } else if let Ok(path) = std::env::var("CHROME_TRACE_FILE") {
                 layer = layer.file(path);
             } else if std::fs::create_dir_all(&out_dir).is_ok() {
                 let time = std::time::SystemTime::now()
@@ -80,13 +111,14 @@ impl Profiler {
         let subscriber = subscriber.with(chrome_layer);
 
         // create dispatcher which will forward span events to the subscriber
-        // this can only be set once or will panic
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("Tracer could not set the global default subscriber.");
+        // this can only be set once, the host or another library may have done so already
+        if let Err(error) = tracing::subscriber::set_global_default(subscriber) {
+            log::warn!("Could not set the global tracing subscriber: {error}");
+        }
 
         Profiler {
             #[cfg(feature = "chrome-trace")]
-            _guard: guard,
+            guard: Mutex::new(guard),
         }
     }
 }
@@ -0,0 +1,293 @@
+//! Normalize and smooth scrolling.
+use std::time::Instant;
+
+use crate::core::mouse::{self, ScrollDelta};
+use crate::core::{Event, Vector};
+use crate::settings::{ScrollSettings, ScrollSmoothing, ScrollUnit};
+
+/// Smooth scrolling stops once less than this many logical pixels are left.
+const MIN_DISTANCE: f32 = 0.5;
+
+/// Kinetic scrolling stops once it is slower than this many logical pixels per
+/// second.
+const MIN_VELOCITY: f32 = 10.0;
+
+/// Applies the [`ScrollSettings`] to the wheel events of an application.
+#[derive(Debug)]
+pub(crate) struct Scroll {
+    settings: ScrollSettings,
+    /// The distance that still has to be scrolled, in logical pixels.
+    pending: Vector,
+    /// The speed of kinetic scrolling, in logical pixels per second.
+    velocity: Vector,
+    /// The unit of the last wheel event, used for [`ScrollUnit::Native`].
+    native_unit: Option<ScrollDelta>,
+    last_frame: Option<Instant>,
+}
+
+impl Scroll {
+    pub fn new(settings: ScrollSettings) -> Self {
+        Self {
+            settings,
+            pending: Vector::ZERO,
+            velocity: Vector::ZERO,
+            native_unit: None,
+            last_frame: None,
+        }
+    }
+
+    /// Normalizes the wheel events in `events[start..]`.
+    ///
+    /// When smoothing is enabled the events are removed, and their distance is
+    /// delivered over the next frames by [`Scroll::frame`] instead. Returns
+    /// whether any events were removed.
+    pub fn process(&mut self, events: &mut Vec<Event>, start: usize) -> bool {
+        let mut smoothed = false;
+        let mut i = start;
+
+        while i < events.len() {
+            let Event::Mouse(mouse::Event::WheelScrolled { delta }) = events[i] else {
+                i += 1;
+                continue;
+            };
+
+            let pixels = self.pixels(delta);
+            self.native_unit = Some(delta);
+
+            if self.settings.smoothing == ScrollSmoothing::None {
+                events[i] = Event::Mouse(mouse::Event::WheelScrolled {
+                    delta: self.delta(pixels),
+                });
+
+                i += 1;
+            } else {
+                self.pending = self.pending + pixels;
+                smoothed = true;
+
+                let _ = events.remove(i);
+            }
+        }
+
+        smoothed
+    }
+
+    /// Advances the smoothing by one frame, and returns the wheel event to
+    /// deliver during that frame.
+    pub fn frame(&mut self, now: Instant) -> Option<Event> {
+        if self.settings.smoothing == ScrollSmoothing::None {
+            return None;
+        }
+
+        let dt = self
+            .last_frame
+            .replace(now)
+            .map(|last| now.duration_since(last).as_secs_f32().min(0.1))
+            .unwrap_or(1.0 / 60.0);
+
+        let step = match self.settings.smoothing {
+            ScrollSmoothing::None => Vector::ZERO,
+            ScrollSmoothing::Smooth { response } => {
+                let step =
+                    if self.pending.x.abs() < MIN_DISTANCE && self.pending.y.abs() < MIN_DISTANCE {
+                        self.pending
+                    } else {
+                        let fraction = 1.0 - (1.0 - response.clamp(0.0, 1.0)).powf(dt * 60.0);
+
+                        self.pending * fraction
+                    };
+
+                self.pending = self.pending - step;
+
+                step
+            }
+            ScrollSmoothing::Kinetic { friction } => {
+                if self.pending != Vector::ZERO {
+                    // New movement is applied right away and sets the speed.
+                    let step = self.pending;
+
+                    self.velocity = step * (1.0 / dt);
+                    self.pending = Vector::ZERO;
+
+                    step
+                } else {
+                    self.velocity = self.velocity * (1.0 - friction.clamp(0.0, 1.0)).powf(dt);
+
+                    if self.velocity.x.abs() < MIN_VELOCITY && self.velocity.y.abs() < MIN_VELOCITY
+                    {
+                        self.velocity = Vector::ZERO;
+                    }
+
+                    self.velocity * dt
+                }
+            }
+        };
+
+        if step == Vector::ZERO {
+            return None;
+        }
+
+        Some(Event::Mouse(mouse::Event::WheelScrolled {
+            delta: self.delta(step),
+        }))
+    }
+
+    fn pixels(&self, delta: ScrollDelta) -> Vector {
+        let pixels = match delta {
+            ScrollDelta::Lines { x, y } => Vector::new(x, y) * self.settings.pixels_per_line,
+            ScrollDelta::Pixels { x, y } => Vector::new(x, y),
+        };
+
+        if self.settings.invert {
+            -pixels
+        } else {
+            pixels
+        }
+    }
+
+    fn delta(&self, pixels: Vector) -> ScrollDelta {
+        let lines = match self.settings.unit {
+            ScrollUnit::Lines => true,
+            ScrollUnit::Pixels => false,
+            ScrollUnit::Native => {
+                self.settings.smoothing == ScrollSmoothing::None
+                    && matches!(self.native_unit, Some(ScrollDelta::Lines { .. }))
+            }
+        };
+
+        if lines {
+            let lines = pixels * (1.0 / self.settings.pixels_per_line);
+
+            ScrollDelta::Lines {
+                x: lines.x,
+                y: lines.y,
+            }
+        } else {
+            ScrollDelta::Pixels {
+                x: pixels.x,
+                y: pixels.y,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    fn wheel(delta: ScrollDelta) -> Event {
+        Event::Mouse(mouse::Event::WheelScrolled { delta })
+    }
+
+    fn scroll(unit: ScrollUnit, invert: bool, smoothing: ScrollSmoothing) -> Scroll {
+        Scroll::new(ScrollSettings {
+            unit,
+            pixels_per_line: 20.0,
+            invert,
+            smoothing,
+        })
+    }
+
+    /// Runs frames 1/60 s apart until smoothing stops, and returns the
+    /// vertical distance of every frame in pixels.
+    fn frames(scroll: &mut Scroll) -> Vec<f32> {
+        let start = Instant::now();
+        let mut steps = Vec::new();
+
+        for frame in 0..600 {
+            let now = start + Duration::from_secs_f32(frame as f32 / 60.0);
+
+            match scroll.frame(now) {
+                Some(Event::Mouse(mouse::Event::WheelScrolled {
+                    delta: ScrollDelta::Pixels { y, .. },
+                })) => steps.push(y),
+                Some(event) => panic!("unexpected event {event:?}"),
+                None if frame > 0 => break,
+                None => {}
+            }
+        }
+
+        steps
+    }
+
+    #[test]
+    fn converts_units_without_smoothing() {
+        let mut events = vec![wheel(ScrollDelta::Lines { x: 0.0, y: 2.0 })];
+
+        let mut pixels = scroll(ScrollUnit::Pixels, false, ScrollSmoothing::None);
+        assert!(!pixels.process(&mut events, 0));
+        assert_eq!(events, vec![wheel(ScrollDelta::Pixels { x: 0.0, y: 40.0 })]);
+
+        let mut lines = scroll(ScrollUnit::Lines, true, ScrollSmoothing::None);
+        assert!(!lines.process(&mut events, 0));
+        assert_eq!(events, vec![wheel(ScrollDelta::Lines { x: 0.0, y: -2.0 })]);
+
+        let mut native = scroll(ScrollUnit::Native, false, ScrollSmoothing::None);
+        assert!(!native.process(&mut events, 0));
+        assert_eq!(events, vec![wheel(ScrollDelta::Lines { x: 0.0, y: -2.0 })]);
+        assert_eq!(native.frame(Instant::now()), None);
+    }
+
+    #[test]
+    fn only_processes_events_after_start() {
+        let mut events = vec![
+            wheel(ScrollDelta::Lines { x: 0.0, y: 1.0 }),
+            wheel(ScrollDelta::Lines { x: 0.0, y: 1.0 }),
+        ];
+
+        let mut scroll = scroll(ScrollUnit::Pixels, false, ScrollSmoothing::None);
+        let _ = scroll.process(&mut events, 1);
+
+        assert_eq!(
+            events,
+            vec![
+                wheel(ScrollDelta::Lines { x: 0.0, y: 1.0 }),
+                wheel(ScrollDelta::Pixels { x: 0.0, y: 20.0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn smooth_scrolling_delivers_the_whole_distance() {
+        let mut scroll = scroll(
+            ScrollUnit::Native,
+            false,
+            ScrollSmoothing::Smooth { response: 0.3 },
+        );
+        let mut events = vec![wheel(ScrollDelta::Lines { x: 0.0, y: 3.0 })];
+
+        assert!(scroll.process(&mut events, 0));
+        assert!(events.is_empty());
+
+        let steps = frames(&mut scroll);
+
+        // The last step covers the small rest of the distance at once.
+        let (last, steps_before) = steps.split_last().unwrap();
+
+        assert!(steps_before.len() > 1);
+        assert!(steps_before.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(*last < MIN_DISTANCE);
+        assert!((steps.iter().sum::<f32>() - 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn kinetic_scrolling_decays() {
+        let mut scroll = scroll(
+            ScrollUnit::Pixels,
+            false,
+            ScrollSmoothing::Kinetic { friction: 0.95 },
+        );
+        let mut events = vec![wheel(ScrollDelta::Pixels { x: 0.0, y: 10.0 })];
+
+        assert!(scroll.process(&mut events, 0));
+
+        let steps = frames(&mut scroll);
+
+        // The movement itself is applied right away, then it coasts.
+        assert_eq!(steps[0], 10.0);
+        assert!(steps.len() > 2);
+        assert!(steps[1..].windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(steps[1..].iter().all(|step| *step > 0.0));
+    }
+}
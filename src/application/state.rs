@@ -1,10 +1,10 @@
 use baseview::WindowScalePolicy;
 
 use crate::application::{Appearance, Application, DefaultStyle};
+use crate::core::keyboard;
 use crate::core::mouse;
-use crate::core::{Color, Size};
+use crate::core::{Color, Event, Size};
 use crate::graphics::Viewport;
-use crate::runtime::Debug;
 
 use std::marker::PhantomData;
 
@@ -24,7 +24,10 @@ where
 
     system_scale_factor: f64,
     scale_policy: WindowScalePolicy,
-    modifiers: iced_runtime::core::keyboard::Modifiers,
+    host_scale_factor: Option<f64>,
+    modifiers: keyboard::Modifiers,
+    pressed_buttons: Vec<mouse::Button>,
+    cursor_left_while_captured: bool,
 }
 
 impl<A: Application> State<A>
@@ -37,6 +40,7 @@ where
         let theme = application.theme();
         let appearance = application.style(&theme);
         let scale_policy = application.scale_policy();
+        let system_scale_factor = viewport.scale_factor();
 
         Self {
             title,
@@ -47,12 +51,22 @@ where
             appearance,
             application: PhantomData,
 
-            system_scale_factor: 1.0,
+            // Until the window reports its size, assume it uses the scale
+            // factor the viewport was created with.
+            system_scale_factor,
             scale_policy,
+            host_scale_factor: None,
             modifiers: Default::default(),
+            pressed_buttons: Vec::new(),
+            cursor_left_while_captured: false,
         }
     }
 
+    /// Returns the keyboard modifiers that are currently held.
+    pub fn modifiers(&self) -> keyboard::Modifiers {
+        self.modifiers
+    }
+
     /// Returns the current [`Viewport`] of the [`State`].
     pub fn viewport(&self) -> &Viewport {
         &self.viewport
@@ -75,6 +89,40 @@ where
         self.viewport.logical_size()
     }
 
+    /// Changes the physical size of the [`Viewport`], keeping its scale
+    /// factor.
+    pub fn resize(&mut self, physical_size: Size<u32>) {
+        if physical_size == self.viewport.physical_size() {
+            return;
+        }
+
+        self.viewport = Viewport::with_physical_size(physical_size, self.viewport.scale_factor());
+        self.viewport_version = self.viewport_version.wrapping_add(1);
+    }
+
+    /// Returns the scale factor the `baseview` window uses to convert between
+    /// logical and physical sizes.
+    pub fn window_scale_factor(&self) -> f64 {
+        self.system_scale_factor
+    }
+
+    /// Sets the scale factor of the [`Viewport`] as requested by the host,
+    /// keeping its logical size. This overrides [`Application::scale_policy`].
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        let logical_size = self.viewport.logical_size();
+
+        self.host_scale_factor = Some(scale_factor);
+        self.scale_policy = WindowScalePolicy::ScaleFactor(scale_factor);
+        self.viewport = Viewport::with_physical_size(
+            Size::new(
+                (f64::from(logical_size.width) * scale_factor).round() as u32,
+                (f64::from(logical_size.height) * scale_factor).round() as u32,
+            ),
+            scale_factor,
+        );
+        self.viewport_version = self.viewport_version.wrapping_add(1);
+    }
+
     /// Returns the current cursor position of the [`State`].
     pub fn cursor(&self) -> mouse::Cursor {
         self.cursor_position
@@ -82,6 +130,14 @@ where
             .unwrap_or(mouse::Cursor::Unavailable)
     }
 
+    /// Returns `true` while a mouse button is held down.
+    ///
+    /// The pointer is captured by the window for as long as that's the case, so
+    /// a drag keeps receiving mouse events after the cursor leaves the window.
+    pub fn is_pointer_captured(&self) -> bool {
+        !self.pressed_buttons.is_empty()
+    }
+
     /// Returns the current theme of the [`State`].
     pub fn theme(&self) -> &A::Theme {
         &self.theme
@@ -99,15 +155,16 @@ where
 
     /// Processes the provided window event and updates the [`State`]
     /// accordingly.
-    pub fn update(&mut self, event: &baseview::Event, _debug: &mut Debug) {
+    pub fn update(&mut self, event: &baseview::Event) {
         match event {
             baseview::Event::Window(baseview::WindowEvent::Resized(window_info)) => {
                 // Cache system window info in case users changes their scale policy in the future.
                 self.system_scale_factor = window_info.scale();
 
-                let scale = match self.scale_policy {
-                    WindowScalePolicy::ScaleFactor(scale) => scale,
-                    WindowScalePolicy::SystemScaleFactor => self.system_scale_factor,
+                let scale = match (self.host_scale_factor, self.scale_policy) {
+                    (Some(scale), _) => scale,
+                    (None, WindowScalePolicy::ScaleFactor(scale)) => scale,
+                    (None, WindowScalePolicy::SystemScaleFactor) => self.system_scale_factor,
                 };
 
                 self.viewport = Viewport::with_physical_size(
@@ -128,18 +185,11 @@ where
                     x: position.x as f32,
                     y: position.y as f32,
                 });
-
-                // TODO: Encode cursor moving outside of the window.
             }
-            #[allow(unused_variables)]
-            baseview::Event::Keyboard(event) => {
-                #[cfg(feature = "debug")]
-                {
-                    use keyboard_types::{Key, KeyState};
-                    if event.key == Key::F12 && event.state == KeyState::Down {
-                        _debug.toggle();
-                    }
-                }
+            baseview::Event::Mouse(baseview::MouseEvent::CursorLeft)
+                if !self.is_pointer_captured() =>
+            {
+                self.cursor_position = None;
             }
             _ => {}
         }
@@ -162,8 +212,12 @@ where
             self.title = new_title;
         }
 
-        // Update scale policy
-        let new_scale_policy = application.scale_policy();
+        // Update scale policy, unless the host set a scale factor which takes
+        // precedence
+        let new_scale_policy = match self.host_scale_factor {
+            Some(scale) => WindowScalePolicy::ScaleFactor(scale),
+            None => application.scale_policy(),
+        };
 
         match &new_scale_policy {
             WindowScalePolicy::SystemScaleFactor => match &self.scale_policy {
@@ -203,7 +257,267 @@ where
         self.appearance = application.style(&self.theme);
     }
 
-    pub(crate) fn modifiers_mut(&mut self) -> &mut iced_runtime::core::keyboard::Modifiers {
-        &mut self.modifiers
+    /// Converts the provided window event into iced events, applying pointer
+    /// capture.
+    ///
+    /// While the pointer is captured, leaving the window is only reported once
+    /// every mouse button has been released. Losing focus releases all held
+    /// buttons, so widgets don't get stuck in a drag when the release happens
+    /// somewhere we can't see it.
+    pub(crate) fn convert_event(
+        &mut self,
+        event: baseview::Event,
+        events: &mut Vec<Event>,
+        ignore_non_modifier_keys: bool,
+    ) {
+        match &event {
+            baseview::Event::Mouse(baseview::MouseEvent::ButtonPressed { button, .. }) => {
+                let button = crate::conversion::baseview_mouse_button_to_iced(*button);
+
+                if !self.pressed_buttons.contains(&button) {
+                    self.pressed_buttons.push(button);
+                }
+            }
+            baseview::Event::Mouse(baseview::MouseEvent::ButtonReleased { button, .. }) => {
+                let button = crate::conversion::baseview_mouse_button_to_iced(*button);
+
+                self.pressed_buttons.retain(|pressed| *pressed != button);
+            }
+            baseview::Event::Mouse(baseview::MouseEvent::CursorEntered) => {
+                self.cursor_left_while_captured = false;
+            }
+            baseview::Event::Mouse(baseview::MouseEvent::CursorLeft)
+                if self.is_pointer_captured() =>
+            {
+                self.cursor_left_while_captured = true;
+
+                return;
+            }
+            baseview::Event::Window(baseview::WindowEvent::Unfocused) => {
+                self.release_buttons(events);
+            }
+            _ => {}
+        }
+
+        crate::conversion::baseview_to_iced_events(
+            event,
+            events,
+            &mut self.modifiers,
+            ignore_non_modifier_keys,
+        );
+
+        self.report_deferred_leave(events);
+    }
+
+    /// Releases all held mouse buttons, reporting the cursor leaving the
+    /// window afterwards if it did so during the drag.
+    pub(crate) fn release_buttons(&mut self, events: &mut Vec<Event>) {
+        for button in self.pressed_buttons.drain(..) {
+            events.push(Event::Mouse(mouse::Event::ButtonReleased(button)));
+        }
+
+        self.report_deferred_leave(events);
+    }
+
+    /// Reports the cursor leaving the window during a drag once the drag is
+    /// over.
+    fn report_deferred_leave(&mut self, events: &mut Vec<Event>) {
+        if self.cursor_left_while_captured && !self.is_pointer_captured() {
+            self.cursor_left_while_captured = false;
+            self.cursor_position = None;
+
+            events.push(Event::Mouse(mouse::Event::CursorLeft));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Element, Renderer, Task, Theme};
+
+    struct Scaled(WindowScalePolicy);
+
+    impl Application for Scaled {
+        type Message = ();
+        type Theme = Theme;
+        type Executor = crate::executor::Default;
+        type Flags = WindowScalePolicy;
+        type Output = ();
+
+        fn new(scale_policy: WindowScalePolicy) -> (Self, Task<()>) {
+            (Self(scale_policy), Task::none())
+        }
+
+        fn update(&mut self, _message: ()) -> Task<()> {
+            Task::none()
+        }
+
+        fn view(&self) -> Element<'_, (), Theme, Renderer> {
+            crate::widget::Space::new(0, 0).into()
+        }
+
+        fn theme(&self) -> Theme {
+            Theme::Dark
+        }
+
+        fn scale_policy(&self) -> WindowScalePolicy {
+            self.0
+        }
+    }
+
+    fn state(application: &Scaled) -> State<Scaled> {
+        State::new(
+            application,
+            Viewport::with_physical_size(Size::new(200, 100), 1.0),
+        )
+    }
+
+    fn resized(width: u32, height: u32, scale: f64) -> baseview::Event {
+        baseview::Event::Window(baseview::WindowEvent::Resized(
+            baseview::WindowInfo::from_physical_size(baseview::PhySize::new(width, height), scale),
+        ))
+    }
+
+    fn mouse(event: baseview::MouseEvent) -> baseview::Event {
+        baseview::Event::Mouse(event)
+    }
+
+    fn pressed() -> baseview::Event {
+        mouse(baseview::MouseEvent::ButtonPressed {
+            button: baseview::MouseButton::Left,
+            modifiers: keyboard_types::Modifiers::empty(),
+        })
+    }
+
+    fn released() -> baseview::Event {
+        mouse(baseview::MouseEvent::ButtonReleased {
+            button: baseview::MouseButton::Left,
+            modifiers: keyboard_types::Modifiers::empty(),
+        })
+    }
+
+    fn convert(state: &mut State<Scaled>, event: baseview::Event) -> Vec<Event> {
+        let mut events = Vec::new();
+        state.convert_event(event, &mut events, false);
+
+        events
+    }
+
+    #[test]
+    fn leaving_during_a_drag_is_reported_after_the_release() {
+        let application = Scaled(WindowScalePolicy::SystemScaleFactor);
+        let mut state = state(&application);
+
+        let _ = convert(&mut state, pressed());
+        assert!(state.is_pointer_captured());
+
+        assert_eq!(
+            convert(&mut state, mouse(baseview::MouseEvent::CursorLeft)),
+            []
+        );
+
+        assert_eq!(
+            convert(&mut state, released()),
+            [
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)),
+                Event::Mouse(mouse::Event::CursorLeft),
+            ]
+        );
+        assert!(!state.is_pointer_captured());
+    }
+
+    #[test]
+    fn losing_focus_releases_the_held_buttons() {
+        let application = Scaled(WindowScalePolicy::SystemScaleFactor);
+        let mut state = state(&application);
+
+        let _ = convert(&mut state, pressed());
+
+        assert_eq!(
+            convert(
+                &mut state,
+                baseview::Event::Window(baseview::WindowEvent::Unfocused)
+            ),
+            [Event::Mouse(mouse::Event::ButtonReleased(
+                mouse::Button::Left
+            ))]
+        );
+        assert!(!state.is_pointer_captured());
+    }
+
+    #[test]
+    fn leaving_without_a_drag_is_reported_right_away() {
+        let application = Scaled(WindowScalePolicy::SystemScaleFactor);
+        let mut state = state(&application);
+
+        assert_eq!(
+            convert(&mut state, mouse(baseview::MouseEvent::CursorLeft)),
+            [Event::Mouse(mouse::Event::CursorLeft)]
+        );
+    }
+
+    #[test]
+    fn set_scale_factor_keeps_the_logical_size() {
+        let application = Scaled(WindowScalePolicy::SystemScaleFactor);
+        let mut state = state(&application);
+        let version = state.viewport_version();
+
+        state.set_scale_factor(2.0);
+
+        assert_eq!(state.physical_size(), Size::new(400, 200));
+        assert_eq!(state.logical_size(), Size::new(200.0, 100.0));
+        assert_eq!(state.viewport().scale_factor(), 2.0);
+        assert_eq!(state.window_scale_factor(), 1.0);
+        assert_ne!(state.viewport_version(), version);
+    }
+
+    #[test]
+    fn the_host_scale_factor_overrides_the_application() {
+        let application = Scaled(WindowScalePolicy::ScaleFactor(1.5));
+        let mut state = state(&application);
+
+        state.update(&resized(300, 150, 1.0));
+        assert_eq!(state.viewport().scale_factor(), 1.5);
+
+        state.set_scale_factor(2.0);
+        state.synchronize(&application);
+        assert_eq!(state.viewport().scale_factor(), 2.0);
+
+        state.update(&resized(600, 300, 1.25));
+        assert_eq!(state.physical_size(), Size::new(600, 300));
+        assert_eq!(state.viewport().scale_factor(), 2.0);
+        assert_eq!(state.window_scale_factor(), 1.25);
+    }
+
+    #[test]
+    fn the_system_scale_factor_is_followed_without_an_override() {
+        let application = Scaled(WindowScalePolicy::SystemScaleFactor);
+        let mut state = state(&application);
+
+        state.update(&resized(300, 150, 1.5));
+        state.synchronize(&application);
+
+        assert_eq!(state.physical_size(), Size::new(300, 150));
+        assert_eq!(state.viewport().scale_factor(), 1.5);
+        assert_eq!(state.window_scale_factor(), 1.5);
+    }
+
+    #[test]
+    fn resize_keeps_the_scale_factor() {
+        let application = Scaled(WindowScalePolicy::SystemScaleFactor);
+        let mut state = state(&application);
+
+        state.set_scale_factor(2.0);
+        let version = state.viewport_version();
+
+        state.resize(Size::new(400, 200));
+        assert_eq!(state.viewport_version(), version);
+
+        state.resize(Size::new(500, 300));
+        assert_eq!(state.logical_size(), Size::new(250.0, 150.0));
+        assert_eq!(state.viewport().scale_factor(), 2.0);
+        assert_ne!(state.viewport_version(), version);
     }
 }
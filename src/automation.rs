@@ -0,0 +1,409 @@
+//! Drive a running window from another process, for end-to-end tests.
+//!
+//! When [`Settings::automation_socket`] is set, the window listens on a Unix
+//! domain socket at that path. Clients send one command per line and receive
+//! one reply line for each of them: `ok`, `error <reason>`, or the requested
+//! data. Coordinates are in logical pixels.
+//!
+//! | Command | Effect |
+//! | --- | --- |
+//! | `move <x> <y>` | Moves the cursor. |
+//! | `press <button>` | Presses `left`, `right`, `middle`, `back` or `forward`. |
+//! | `release <button>` | Releases a mouse button. |
+//! | `scroll <x> <y>` | Scrolls by the given number of lines. |
+//! | `key_down <key> [<code>]` | Presses a key, named like `keyboard_types::Key` and `Code`. |
+//! | `key_up <key> [<code>]` | Releases a key. |
+//! | `message <text>` | Sends the message returned by [`Application::automation_message`]. |
+//! | `size` | Replies with `size <width> <height> <scale factor>`. |
+//! | `screenshot <path>` | Writes the window contents to a PAM image at `path`. |
+//!
+//! Commands are handled on the next frame of the window.
+//!
+//! [`Settings::automation_socket`]: crate::Settings::automation_socket
+//! [`Application::automation_message`]: crate::Application::automation_message
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use crate::core::Size;
+
+/// A command received through the socket, to be handled by the window.
+#[derive(Debug)]
+pub(crate) enum Request {
+    Event(baseview::Event),
+    Message(String),
+    Size,
+    Screenshot,
+}
+
+/// The answer of the window to a [`Request`].
+#[derive(Debug)]
+pub(crate) enum Response {
+    Ok,
+    Size { size: Size, scale_factor: f64 },
+    Screenshot { size: Size<u32>, rgba: Vec<u8> },
+    Error(String),
+}
+
+/// A [`Request`] together with the channel to send its [`Response`] through.
+pub(crate) type Envelope = (Request, mpsc::Sender<Response>);
+
+/// The listening socket of a window.
+#[derive(Debug)]
+pub(crate) struct Endpoint {
+    path: PathBuf,
+    requests: mpsc::Receiver<Envelope>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Endpoint {
+    /// Starts listening at `path`, replacing any stale socket left there.
+    ///
+    /// Fails rather than removing anything at `path` that isn't a socket.
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        let listener = UnixListener::bind(path)?;
+        let (sender, requests) = mpsc::channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let _ = thread::Builder::new()
+            .name(String::from("iced_baseview automation"))
+            .spawn({
+                let shutdown = Arc::clone(&shutdown);
+
+                move || listen(listener, sender, shutdown)
+            })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            requests,
+            shutdown,
+        })
+    }
+
+    /// Returns the next request that hasn't been handled yet.
+    pub fn try_recv(&self) -> Option<Envelope> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+
+        // Wake up the listener thread so it notices the shutdown.
+        let _ = UnixStream::connect(&self.path);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn listen(listener: UnixListener, sender: mpsc::Sender<Envelope>, shutdown: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+
+        match stream {
+            Ok(stream) => {
+                let sender = sender.clone();
+
+                let _ = thread::spawn(move || {
+                    if let Err(error) = serve(stream, sender) {
+                        log::debug!("Automation connection closed: {error}");
+                    }
+                });
+            }
+            Err(error) => {
+                log::warn!("Failed to accept an automation connection: {error}");
+            }
+        }
+    }
+}
+
+fn serve(stream: UnixStream, sender: mpsc::Sender<Envelope>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let reply = match parse(line) {
+            Ok((request, screenshot_path)) => {
+                let (response_sender, response) = mpsc::channel();
+
+                if sender.send((request, response_sender)).is_err() {
+                    // The window was closed.
+                    return Ok(());
+                }
+
+                match response.recv() {
+                    Ok(response) => reply(response, screenshot_path.as_deref()),
+                    Err(_) => return Ok(()),
+                }
+            }
+            Err(error) => format!("error {error}"),
+        };
+
+        writeln!(writer, "{reply}")?;
+    }
+
+    Ok(())
+}
+
+/// Parses a command, returning the path to write the screenshot to for
+/// `screenshot` commands.
+fn parse(line: &str) -> Result<(Request, Option<PathBuf>), String> {
+    let (command, arguments) = line.split_once(' ').unwrap_or((line, ""));
+    let mut words = arguments.split_whitespace();
+
+    let modifiers = keyboard_types::Modifiers::empty();
+
+    let request = match command {
+        "move" => {
+            let x = number(words.next())?;
+            let y = number(words.next())?;
+
+            Request::Event(baseview::Event::Mouse(baseview::MouseEvent::CursorMoved {
+                position: baseview::Point::new(x, y),
+                modifiers,
+            }))
+        }
+        "press" => Request::Event(baseview::Event::Mouse(
+            baseview::MouseEvent::ButtonPressed {
+                button: button(words.next())?,
+                modifiers,
+            },
+        )),
+        "release" => Request::Event(baseview::Event::Mouse(
+            baseview::MouseEvent::ButtonReleased {
+                button: button(words.next())?,
+                modifiers,
+            },
+        )),
+        "scroll" => {
+            let x = number(words.next())? as f32;
+            let y = number(words.next())? as f32;
+
+            Request::Event(baseview::Event::Mouse(
+                baseview::MouseEvent::WheelScrolled {
+                    delta: baseview::ScrollDelta::Lines { x, y },
+                    modifiers,
+                },
+            ))
+        }
+        "key_down" | "key_up" => {
+            let key = words.next().ok_or("missing key")?;
+            let key = keyboard_types::Key::from_str(key).map_err(|_| "unknown key")?;
+            let code = match words.next() {
+                Some(code) => keyboard_types::Code::from_str(code).map_err(|_| "unknown code")?,
+                None => keyboard_types::Code::Unidentified,
+            };
+
+            Request::Event(baseview::Event::Keyboard(keyboard_types::KeyboardEvent {
+                state: if command == "key_down" {
+                    keyboard_types::KeyState::Down
+                } else {
+                    keyboard_types::KeyState::Up
+                },
+                key,
+                code,
+                location: keyboard_types::Location::Standard,
+                modifiers,
+                repeat: false,
+                is_composing: false,
+            }))
+        }
+        "message" => Request::Message(arguments.to_string()),
+        "size" => Request::Size,
+        "screenshot" => {
+            if arguments.is_empty() {
+                return Err(String::from("missing path"));
+            }
+
+            return Ok((Request::Screenshot, Some(PathBuf::from(arguments))));
+        }
+        _ => return Err(format!("unknown command {command}")),
+    };
+
+    Ok((request, None))
+}
+
+fn number(word: Option<&str>) -> Result<f64, String> {
+    let word = word.ok_or("missing number")?;
+
+    word.parse().map_err(|_| format!("invalid number {word}"))
+}
+
+fn button(word: Option<&str>) -> Result<baseview::MouseButton, String> {
+    match word.ok_or("missing button")? {
+        "left" => Ok(baseview::MouseButton::Left),
+        "right" => Ok(baseview::MouseButton::Right),
+        "middle" => Ok(baseview::MouseButton::Middle),
+        "back" => Ok(baseview::MouseButton::Back),
+        "forward" => Ok(baseview::MouseButton::Forward),
+        word => Err(format!("unknown button {word}")),
+    }
+}
+
+/// Formats the reply to a [`Response`], writing screenshots to disk.
+fn reply(response: Response, screenshot_path: Option<&Path>) -> String {
+    match response {
+        Response::Ok => String::from("ok"),
+        Response::Size { size, scale_factor } => {
+            format!("size {} {} {scale_factor}", size.width, size.height)
+        }
+        Response::Screenshot { size, rgba } => {
+            let Some(path) = screenshot_path else {
+                return String::from("error missing path");
+            };
+
+            match write_pam(path, size, &rgba) {
+                Ok(()) => String::from("ok"),
+                Err(error) => format!("error {error}"),
+            }
+        }
+        Response::Error(error) => format!("error {error}"),
+    }
+}
+
+/// Writes RGBA pixels as a PAM image, which needs no encoder and is read by
+/// most image tools.
+fn write_pam(path: &Path, size: Size<u32>, rgba: &[u8]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+
+    write!(
+        file,
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+        size.width, size.height
+    )?;
+    file.write_all(rgba)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(line: &str) -> baseview::Event {
+        match parse(line) {
+            Ok((Request::Event(event), None)) => event,
+            other => panic!("{line:?} parsed as {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_mouse_commands() {
+        assert!(matches!(
+            event("move 10 20.5"),
+            baseview::Event::Mouse(baseview::MouseEvent::CursorMoved { position, .. })
+                if position.x == 10.0 && position.y == 20.5
+        ));
+        assert!(matches!(
+            event("press right"),
+            baseview::Event::Mouse(baseview::MouseEvent::ButtonPressed {
+                button: baseview::MouseButton::Right,
+                ..
+            })
+        ));
+        assert!(matches!(
+            event("release left"),
+            baseview::Event::Mouse(baseview::MouseEvent::ButtonReleased {
+                button: baseview::MouseButton::Left,
+                ..
+            })
+        ));
+        assert!(matches!(
+            event("scroll 0 -2"),
+            baseview::Event::Mouse(baseview::MouseEvent::WheelScrolled {
+                delta: baseview::ScrollDelta::Lines { x, y },
+                ..
+            }) if x == 0.0 && y == -2.0
+        ));
+    }
+
+    #[test]
+    fn parses_key_commands() {
+        let baseview::Event::Keyboard(down) = event("key_down a KeyA") else {
+            panic!("not a keyboard event");
+        };
+
+        assert_eq!(down.state, keyboard_types::KeyState::Down);
+        assert_eq!(down.key, keyboard_types::Key::Character(String::from("a")));
+        assert_eq!(down.code, keyboard_types::Code::KeyA);
+
+        let baseview::Event::Keyboard(up) = event("key_up Enter") else {
+            panic!("not a keyboard event");
+        };
+
+        assert_eq!(up.state, keyboard_types::KeyState::Up);
+        assert_eq!(up.key, keyboard_types::Key::Enter);
+        assert_eq!(up.code, keyboard_types::Code::Unidentified);
+    }
+
+    #[test]
+    fn parses_window_commands() {
+        assert!(matches!(
+            parse("message set gain 0.5"),
+            Ok((Request::Message(text), None)) if text == "set gain 0.5"
+        ));
+        assert!(matches!(parse("size"), Ok((Request::Size, None))));
+        assert!(matches!(
+            parse("screenshot /tmp/a b.pam"),
+            Ok((Request::Screenshot, Some(path))) if path == Path::new("/tmp/a b.pam")
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        for line in [
+            "",
+            "jump",
+            "move 1",
+            "move one 2",
+            "press",
+            "press thumb",
+            "key_down",
+            "key_down NotAKey",
+            "key_down a NotACode",
+            "screenshot",
+        ] {
+            assert!(parse(line).is_err(), "{line:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn bind_keeps_files_that_are_not_sockets() {
+        let path = std::env::temp_dir().join(format!(
+            "iced_baseview-automation-{}.txt",
+            std::process::id()
+        ));
+        std::fs::write(&path, "data").unwrap();
+
+        let error = Endpoint::bind(&path).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -63,6 +63,12 @@ pub fn baseview_to_iced_events(
                     }));
                 }
             },
+            baseview::MouseEvent::CursorEntered => {
+                iced_events.push(IcedEvent::Mouse(IcedMouseEvent::CursorEntered));
+            }
+            baseview::MouseEvent::CursorLeft => {
+                iced_events.push(IcedEvent::Mouse(IcedMouseEvent::CursorLeft));
+            }
             _ => {}
         },
 
@@ -136,37 +142,43 @@ fn update_modifiers(
     iced_modifiers: &mut IcedModifiers,
     baseview_modifiers: BaseviewModifiers,
 ) -> Option<IcedEvent> {
-    let mut new = IcedModifiers::default();
+    let new = baseview_to_iced_modifiers(baseview_modifiers);
+
+    if *iced_modifiers != new {
+        *iced_modifiers = new;
 
-    new.set(
+        Some(IcedEvent::Keyboard(
+            iced_runtime::core::keyboard::Event::ModifiersChanged(*iced_modifiers),
+        ))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn baseview_to_iced_modifiers(baseview_modifiers: BaseviewModifiers) -> IcedModifiers {
+    let mut modifiers = IcedModifiers::default();
+
+    modifiers.set(
         IcedModifiers::ALT,
         baseview_modifiers.contains(BaseviewModifiers::ALT),
     );
-    new.set(
+    modifiers.set(
         IcedModifiers::CTRL,
         baseview_modifiers.contains(BaseviewModifiers::CONTROL),
     );
-    new.set(
+    modifiers.set(
         IcedModifiers::SHIFT,
         baseview_modifiers.contains(BaseviewModifiers::SHIFT),
     );
-    new.set(
+    modifiers.set(
         IcedModifiers::LOGO,
         baseview_modifiers.contains(BaseviewModifiers::META),
     );
 
-    if *iced_modifiers != new {
-        *iced_modifiers = new;
-
-        Some(IcedEvent::Keyboard(
-            iced_runtime::core::keyboard::Event::ModifiersChanged(*iced_modifiers),
-        ))
-    } else {
-        None
-    }
+    modifiers
 }
 
-fn baseview_mouse_button_to_iced(id: baseview::MouseButton) -> IcedMouseButton {
+pub(crate) fn baseview_mouse_button_to_iced(id: baseview::MouseButton) -> IcedMouseButton {
     use baseview::MouseButton;
 
     match id {
@@ -215,7 +227,7 @@ fn baseview_key_location_to_iced(
     }
 }
 
-fn baseview_to_iced_key(key: keyboard_types::Key) -> iced_runtime::core::keyboard::Key {
+pub(crate) fn baseview_to_iced_key(key: keyboard_types::Key) -> iced_runtime::core::keyboard::Key {
     use iced_runtime::core::keyboard::key::Named as IN;
     use iced_runtime::core::keyboard::Key as IKey;
     use keyboard_types::Key as KKey;
@@ -0,0 +1,519 @@
+//! Embed an [`Application`] as a plugin editor.
+//!
+//! Plugin formats all ask the same things of an editor: open it inside a
+//! window of the host, tell how large it is, follow the scale factor and the
+//! parameter changes of the host, and close it again. The [`Editor`] trait
+//! covers those, so a CLAP, VST3 or nih-plug wrapper only has to translate the
+//! calls of its format. [`IcedEditor`] implements it for any [`Application`].
+use std::mem::ManuallyDrop;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+
+use iced_runtime::futures::futures::channel::mpsc;
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+use crate::core::Size;
+use crate::param::ParamId;
+use crate::realtime::{self, OverflowPolicy};
+use crate::window::{RuntimeEvent, WindowHandle};
+use crate::{Application, Settings};
+
+/// How many parameter changes the host can make between two frames of the
+/// editor before the oldest ones are dropped.
+const PARAM_QUEUE_CAPACITY: usize = 1024;
+
+/// A plugin editor, as seen by the glue code of a plugin format.
+///
+/// Plugin formats like nih-plug move the editor between threads, so it has to
+/// be [`Send`]. Most methods are called on the thread of the host's user
+/// interface, but hosts report parameter changes from any thread, including
+/// the audio thread, and may close the editor from another thread too.
+pub trait Editor: Send {
+    /// Opens the editor inside the `parent` window of the host, closing the
+    /// editor first if it's already open.
+    fn spawn(&mut self, parent: &dyn HasRawWindowHandle);
+
+    /// Returns whether the editor is open.
+    fn is_open(&self) -> bool;
+
+    /// Closes the editor.
+    fn close(&mut self);
+
+    /// Stops drawing the editor while the host hides it without closing it.
+    fn suspend(&mut self);
+
+    /// Resumes drawing the editor when the host shows it again.
+    fn resume(&mut self);
+
+    /// Returns the logical size the editor opens with.
+    fn preferred_size(&self) -> Size;
+
+    /// Returns the current logical size of the editor.
+    fn size(&self) -> Size;
+
+    /// Returns whether the host may resize the editor.
+    fn can_resize(&self) -> bool;
+
+    /// Returns the logical size the editor would take if the host resized it
+    /// to `requested`, like CLAP's `adjust_size` asks for.
+    fn constrain_size(&self, requested: Size) -> Size;
+
+    /// Resizes the editor to a logical size requested by the host, and returns
+    /// the size it actually takes.
+    fn resize(&mut self, size: Size) -> Size;
+
+    /// Sets the scale factor the host wants the editor to use. Returns `false`
+    /// if the editor follows the scale factor of the system instead, which it
+    /// always does on macOS.
+    fn set_scale_factor(&mut self, scale_factor: f64) -> bool;
+
+    /// Tells the editor that the host changed the normalized value of a
+    /// parameter, for instance through automation. This may be called from
+    /// the audio thread, so it must not lock or allocate.
+    fn param_value_changed(&mut self, id: ParamId, value: f32);
+
+    /// Tells the editor that the host modulates a parameter by a normalized
+    /// offset. This may be called from the audio thread, so it must not lock
+    /// or allocate.
+    fn param_modulation_changed(&mut self, id: ParamId, offset: f32);
+}
+
+/// An [`Editor`] that runs an [`Application`] in a child window.
+///
+/// The flags and settings are created anew every time the editor is opened,
+/// since the host may open and close it many times.
+///
+/// The window can only be reached from the thread that opened it, since a
+/// `baseview` window handle isn't [`Send`]. On any other thread, the editor
+/// behaves as if it was closed, except that:
+///
+/// - Parameter changes go through a [`realtime::channel`] that is delivered
+///   on every frame, so they work from any thread, including the audio thread.
+///   The messages for them are created on the calling thread, and only the
+///   latest 1024 changes between two frames are kept.
+/// - Closing the editor asks the window to close itself on its own thread.
+#[allow(missing_debug_implementations)]
+pub struct IcedEditor<A: Application> {
+    flags: Box<dyn Fn() -> A::Flags + Send>,
+    settings: Box<dyn Fn() -> Settings + Send>,
+    preferred_size: Size,
+    follows_system_scale: bool,
+    scale_factor: Option<f64>,
+    can_resize: bool,
+    on_param_value_changed: Option<Box<dyn Fn(ParamId, f32) -> Option<A::Message> + Send>>,
+    on_param_modulation_changed: Option<Box<dyn Fn(ParamId, f32) -> Option<A::Message> + Send>>,
+    on_output: Arc<Mutex<dyn FnMut(A::Output) + Send>>,
+    param_sender: realtime::Sender<A::Message>,
+    param_receiver: realtime::Receiver<A::Message>,
+    window: Option<OpenWindow<A::Message>>,
+}
+
+/// The window opened by an [`IcedEditor`].
+struct OpenWindow<Message: 'static + Send> {
+    handle: ThreadBound<WindowHandle<Message>>,
+    /// Reaches the window from any thread.
+    sender: mpsc::UnboundedSender<RuntimeEvent<Message>>,
+}
+
+impl<A> IcedEditor<A>
+where
+    A: Application + Send + 'static,
+    A::Flags: Send,
+{
+    /// Creates a new [`IcedEditor`], which opens with the flags and settings
+    /// returned by the given functions.
+    ///
+    /// The host can only set the scale factor if the settings use
+    /// [`WindowScalePolicy::ScaleFactor`](baseview::WindowScalePolicy::ScaleFactor).
+    /// Otherwise the editor follows the scale factor of the system.
+    pub fn new(
+        flags: impl Fn() -> A::Flags + Send + 'static,
+        settings: impl Fn() -> Settings + Send + 'static,
+    ) -> Self {
+        let window = settings().window;
+        let (param_sender, param_receiver) =
+            realtime::channel(PARAM_QUEUE_CAPACITY, OverflowPolicy::DropOldest);
+
+        Self {
+            flags: Box::new(flags),
+            settings: Box::new(settings),
+            preferred_size: Size::new(window.size.width as f32, window.size.height as f32),
+            follows_system_scale: cfg!(target_os = "macos")
+                || matches!(window.scale, baseview::WindowScalePolicy::SystemScaleFactor),
+            scale_factor: None,
+            can_resize: false,
+            on_param_value_changed: None,
+            on_param_modulation_changed: None,
+            on_output: Arc::new(Mutex::new(|_| {})),
+            param_sender,
+            param_receiver,
+            window: None,
+        }
+    }
+
+    /// Sets whether the host may resize the editor.
+    pub fn resizable(mut self, can_resize: bool) -> Self {
+        self.can_resize = can_resize;
+        self
+    }
+
+    /// Sets the message to send when the host changed the value of a
+    /// parameter. The function is called on the thread the host reports the
+    /// change from, which may be the audio thread.
+    pub fn on_param_value_changed(
+        mut self,
+        message: impl Fn(ParamId, f32) -> Option<A::Message> + Send + 'static,
+    ) -> Self {
+        self.on_param_value_changed = Some(Box::new(message));
+        self
+    }
+
+    /// Sets the message to send when the host changed the modulation of a
+    /// parameter. The function is called on the thread the host reports the
+    /// change from, which may be the audio thread.
+    pub fn on_param_modulation_changed(
+        mut self,
+        message: impl Fn(ParamId, f32) -> Option<A::Message> + Send + 'static,
+    ) -> Self {
+        self.on_param_modulation_changed = Some(Box::new(message));
+        self
+    }
+
+    /// Sets the callback that receives the values the application emits with
+    /// [`window::emit`](crate::window::emit).
+    pub fn on_output(mut self, on_output: impl FnMut(A::Output) + Send + 'static) -> Self {
+        self.on_output = Arc::new(Mutex::new(on_output));
+        self
+    }
+
+    /// Returns the handle of the open window, if called from the thread that
+    /// opened it.
+    pub fn handle(&mut self) -> Option<&mut WindowHandle<A::Message>> {
+        self.window
+            .as_mut()
+            .and_then(|window| window.handle.get_mut())
+            .filter(|handle| handle.is_open())
+    }
+
+    fn open_handle(&self) -> Option<&WindowHandle<A::Message>> {
+        self.window
+            .as_ref()
+            .and_then(|window| window.handle.get())
+            .filter(|handle| handle.is_open())
+    }
+
+    /// Queues a parameter change for the window without locking or
+    /// allocating.
+    fn send_param_message(&mut self, message: Option<A::Message>) {
+        if let Some(message) = message {
+            let _ = self.param_sender.send(message);
+        }
+    }
+}
+
+impl<A> Editor for IcedEditor<A>
+where
+    A: Application + Send + 'static,
+    A::Flags: Send,
+{
+    fn spawn(&mut self, parent: &dyn HasRawWindowHandle) {
+        self.close();
+
+        let mut settings = (self.settings)();
+
+        if let Some(scale_factor) = self.scale_factor {
+            settings.window.scale = baseview::WindowScalePolicy::ScaleFactor(scale_factor);
+        }
+
+        let on_output = Arc::clone(&self.on_output);
+
+        let mut handle = crate::open_parented_with_output::<A, _>(
+            &Parent(parent.raw_window_handle()),
+            (self.flags)(),
+            settings,
+            move |output| {
+                (on_output.lock().unwrap_or_else(|e| e.into_inner()))(output);
+            },
+        );
+
+        // The application starts from its flags, so the changes queued while
+        // the editor was closed are outdated.
+        drop(self.param_receiver.drain());
+        let _ = handle.attach_receiver(self.param_receiver.clone());
+
+        self.window = Some(OpenWindow {
+            sender: handle.sender(),
+            handle: ThreadBound::new(handle),
+        });
+    }
+
+    fn is_open(&self) -> bool {
+        self.open_handle().is_some()
+    }
+
+    fn close(&mut self) {
+        self.close_window();
+    }
+
+    fn suspend(&mut self) {
+        if let Some(handle) = self.handle() {
+            let _ = handle.suspend();
+        }
+    }
+
+    fn resume(&mut self) {
+        if let Some(handle) = self.handle() {
+            let _ = handle.resume();
+        }
+    }
+
+    fn preferred_size(&self) -> Size {
+        self.preferred_size
+    }
+
+    fn size(&self) -> Size {
+        self.open_handle()
+            .and_then(WindowHandle::size)
+            .unwrap_or(self.preferred_size)
+    }
+
+    fn can_resize(&self) -> bool {
+        self.can_resize
+    }
+
+    fn constrain_size(&self, requested: Size) -> Size {
+        if !self.can_resize {
+            return self.size();
+        }
+
+        match self.open_handle() {
+            Some(handle) => handle.constrain_size(requested),
+            None => requested,
+        }
+    }
+
+    fn resize(&mut self, size: Size) -> Size {
+        if !self.can_resize {
+            return self.size();
+        }
+
+        match self.handle().map(|handle| handle.resize(size)) {
+            Some(Ok(size)) => size,
+            Some(Err(_)) | None => self.size(),
+        }
+    }
+
+    fn set_scale_factor(&mut self, scale_factor: f64) -> bool {
+        if self.follows_system_scale {
+            return false;
+        }
+
+        // Also keep it for the next time the window is opened.
+        self.scale_factor = Some(scale_factor);
+
+        if let Some(handle) = self.handle() {
+            let _ = handle.set_scale_factor(scale_factor);
+        }
+
+        true
+    }
+
+    fn param_value_changed(&mut self, id: ParamId, value: f32) {
+        let message = self
+            .on_param_value_changed
+            .as_ref()
+            .and_then(|message| message(id, value));
+
+        self.send_param_message(message);
+    }
+
+    fn param_modulation_changed(&mut self, id: ParamId, offset: f32) {
+        let message = self
+            .on_param_modulation_changed
+            .as_ref()
+            .and_then(|message| message(id, offset));
+
+        self.send_param_message(message);
+    }
+}
+
+impl<A: Application> IcedEditor<A> {
+    fn close_window(&mut self) {
+        let Some(mut window) = self.window.take() else {
+            return;
+        };
+
+        match window.handle.get_mut() {
+            Some(handle) => {
+                if handle.is_open() {
+                    handle.close_window();
+                }
+            }
+            // The handle can't be used on this thread, so the window closes
+            // itself on its own thread instead. The handle is leaked.
+            None => {
+                let _ = window.sender.unbounded_send(RuntimeEvent::Close);
+            }
+        }
+    }
+}
+
+impl<A: Application> Drop for IcedEditor<A> {
+    fn drop(&mut self) {
+        self.close_window();
+    }
+}
+
+// Plugin wrappers like nih-plug need the editor of any application to be
+// `Send`, which this fails to compile otherwise.
+const _: () = {
+    fn assert_send<T: Send>() {}
+
+    #[allow(dead_code)]
+    fn assert_editor_is_send<A>()
+    where
+        A: Application + Send + 'static,
+        A::Flags: Send,
+    {
+        assert_send::<IcedEditor<A>>();
+    }
+};
+
+/// The window of the host, as passed to [`Editor::spawn`].
+struct Parent(RawWindowHandle);
+
+unsafe impl HasRawWindowHandle for Parent {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.0
+    }
+}
+
+/// A value that can be moved to other threads, but only used and dropped on
+/// the thread that created it.
+///
+/// A value dropped on another thread is leaked instead, since its destructor
+/// can't run there.
+struct ThreadBound<T> {
+    value: ManuallyDrop<T>,
+    thread: ThreadId,
+}
+
+// SAFETY: The value is only ever accessed on the thread that created it.
+unsafe impl<T> Send for ThreadBound<T> {}
+
+impl<T> ThreadBound<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            thread: thread::current().id(),
+        }
+    }
+
+    fn get(&self) -> Option<&T> {
+        (thread::current().id() == self.thread).then_some(&*self.value)
+    }
+
+    fn get_mut(&mut self) -> Option<&mut T> {
+        (thread::current().id() == self.thread).then_some(&mut *self.value)
+    }
+}
+
+impl<T> Drop for ThreadBound<T> {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            // SAFETY: The value is never used again.
+            unsafe { ManuallyDrop::drop(&mut self.value) };
+        } else {
+            log::debug!("Leaking a value dropped on another thread than its own");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::{Element, Renderer, Task, Theme};
+
+    struct Knobs;
+
+    impl Application for Knobs {
+        type Message = (ParamId, f32);
+        type Theme = Theme;
+        type Executor = crate::executor::Default;
+        type Flags = ();
+        type Output = ();
+
+        fn new(_flags: ()) -> (Self, Task<Self::Message>) {
+            (Self, Task::none())
+        }
+
+        fn update(&mut self, _message: Self::Message) -> Task<Self::Message> {
+            Task::none()
+        }
+
+        fn view(&self) -> Element<'_, Self::Message, Theme, Renderer> {
+            crate::widget::Space::new(0, 0).into()
+        }
+
+        fn theme(&self) -> Theme {
+            Theme::Dark
+        }
+    }
+
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn thread_bound_values_are_only_reachable_on_their_thread() {
+        let mut value = ThreadBound::new(1);
+
+        assert_eq!(value.get(), Some(&1));
+        assert_eq!(value.get_mut(), Some(&mut 1));
+
+        thread::spawn(move || {
+            assert_eq!(value.get(), None);
+            assert_eq!(value.get_mut(), None);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn thread_bound_values_are_only_dropped_on_their_thread() {
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        drop(ThreadBound::new(SetOnDrop(Arc::clone(&dropped))));
+        assert!(dropped.swap(false, Ordering::Relaxed));
+
+        let value = ThreadBound::new(SetOnDrop(Arc::clone(&dropped)));
+        thread::spawn(move || drop(value)).join().unwrap();
+        assert!(!dropped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn param_changes_are_queued_from_any_thread() {
+        let mut editor = IcedEditor::<Knobs>::new(|| (), Settings::default)
+            .on_param_value_changed(|id, value| Some((id, value)))
+            .on_param_modulation_changed(|id, offset| (offset != 0.0).then_some((id, -offset)));
+        let receiver = editor.param_receiver.clone();
+
+        thread::spawn(move || {
+            editor.param_value_changed(1, 0.5);
+            editor.param_modulation_changed(2, 0.0);
+            editor.param_modulation_changed(2, 0.25);
+            editor.close();
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(receiver.drain(), vec![(1, 0.5), (2, -0.25)]);
+    }
+}
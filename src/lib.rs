@@ -41,16 +41,25 @@ pub mod task {
 pub mod application;
 pub mod clipboard;
 pub mod conversion;
+pub mod editor;
 pub mod settings;
 pub mod window;
 
 #[cfg(feature = "system")]
 pub mod system;
 
+#[cfg(all(unix, feature = "automation"))]
+pub mod automation;
+
+pub mod param;
+pub mod realtime;
+
 mod error;
 mod position;
 mod proxy;
 
+#[cfg(feature = "trace")]
+pub use application::Profiler;
 pub use application::{Appearance, Application, DefaultStyle};
 pub use clipboard::Clipboard;
 pub use error::Error;
@@ -58,11 +67,11 @@ pub use event::Event;
 pub use executor::Executor;
 pub use font::Font;
 pub use position::Position;
-#[cfg(feature = "trace")]
-pub use program::Profiler;
 pub use proxy::Proxy;
 pub use renderer::Renderer;
-pub use settings::{GraphicsSettings, IcedBaseviewSettings, Settings};
+pub use settings::{
+    DebugSettings, GraphicsSettings, IcedBaseviewSettings, ScrollSettings, Settings,
+};
 pub use task::Task;
 pub use window::WindowSubs;
 
@@ -137,6 +146,14 @@ pub mod widget {
     //! Use the built-in widgets or create your own.
     pub use iced_widget::*;
 
+    #[cfg(all(feature = "tiny-skia", feature = "image"))]
+    mod cached;
+
+    #[cfg(all(feature = "tiny-skia", feature = "image"))]
+    pub(crate) use cached::set_scale_factor;
+    #[cfg(all(feature = "tiny-skia", feature = "image"))]
+    pub use cached::{cached, Cached};
+
     // We hide the re-exported modules by `iced_widget`
     mod core {}
     mod graphics {}
@@ -166,7 +183,25 @@ where
     A::Flags: Send,
     W: raw_window_handle::HasRawWindowHandle,
 {
-    window::IcedWindow::<A>::open_parented::<W, Compositor>(parent, flags, settings)
+    window::IcedWindow::<A, Compositor>::open_parented(parent, flags, settings)
+}
+
+/// Runs the [`Application`] in a child window, and calls `on_output` on the
+/// window thread with every value it emits with [`window::emit`].
+pub fn open_parented_with_output<A, W>(
+    parent: &W,
+    flags: A::Flags,
+    settings: Settings,
+    on_output: impl FnMut(A::Output) + Send + 'static,
+) -> window::WindowHandle<A::Message>
+where
+    A: Application + Send + 'static,
+    A::Flags: Send,
+    W: raw_window_handle::HasRawWindowHandle,
+{
+    window::IcedWindow::<A, Compositor>::open_parented_with_output(
+        parent, flags, settings, on_output,
+    )
 }
 
 pub fn open_blocking<A>(flags: A::Flags, settings: Settings)
@@ -174,5 +209,5 @@ where
     A: Application + Send + 'static,
     A::Flags: Send,
 {
-    window::IcedWindow::<A>::open_blocking::<Compositor>(flags, settings)
+    window::IcedWindow::<A, Compositor>::open_blocking(flags, settings)
 }
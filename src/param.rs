@@ -0,0 +1,327 @@
+//! Model plugin parameters and report their automation gestures to the host.
+//!
+//! Hosts record automation between a begin and an end gesture, so every change
+//! made from the editor has to be wrapped in one. A [`Control`] turns drags,
+//! double-clicks and mouse wheel changes on its content into correctly paired
+//! [`Gesture`]s, and hands them to the callback of a [`Host`].
+//!
+//! The [`Host`] also pairs the gestures it receives by itself: nested begin
+//! gestures are ignored, and values set outside of a gesture are wrapped in
+//! one. A [`Control`] ends its gesture when it disappears in the middle of a
+//! drag, including when the window closes, and [`Host::end_all`] ends any
+//! gesture left open by other means.
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+mod control;
+
+pub use control::{control, Control};
+
+/// The identifier of a parameter, as known by the host.
+pub type ParamId = u32;
+
+/// A parameter of the plugin, as shown by the editor.
+///
+/// All values are normalized to the `0.0..=1.0` range.
+#[derive(Clone)]
+pub struct Param {
+    id: ParamId,
+    value: f32,
+    default: f32,
+    steps: Option<u32>,
+    format: Option<Arc<dyn Fn(f32) -> String + Send + Sync>>,
+}
+
+impl Param {
+    /// Creates a new [`Param`] with the given id and normalized value. The
+    /// default value is `0.0`, and the value is continuous.
+    pub fn new(id: ParamId, value: f32) -> Self {
+        Self {
+            id,
+            value: value.clamp(0.0, 1.0),
+            default: 0.0,
+            steps: None,
+            format: None,
+        }
+    }
+
+    /// Sets the normalized value that a double-click resets the [`Param`] to.
+    pub fn default(mut self, default: f32) -> Self {
+        self.default = default.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Makes the [`Param`] discrete, with the given number of steps between
+    /// `0.0` and `1.0`. A toggle has a single step.
+    pub fn steps(mut self, steps: u32) -> Self {
+        self.steps = (steps > 0).then_some(steps);
+        self
+    }
+
+    /// Sets the function that formats a normalized value for display.
+    pub fn format(mut self, format: impl Fn(f32) -> String + Send + Sync + 'static) -> Self {
+        self.format = Some(Arc::new(format));
+        self
+    }
+
+    /// Returns the id of the [`Param`].
+    pub fn id(&self) -> ParamId {
+        self.id
+    }
+
+    /// Returns the normalized value of the [`Param`].
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Sets the normalized value of the [`Param`], for instance after the host
+    /// automated it.
+    pub fn set_value(&mut self, value: f32) {
+        self.value = self.snap(value);
+    }
+
+    /// Returns the normalized default value of the [`Param`].
+    pub fn default_value(&self) -> f32 {
+        self.default
+    }
+
+    /// Returns the number of steps of a discrete [`Param`].
+    pub fn step_count(&self) -> Option<u32> {
+        self.steps
+    }
+
+    /// Clamps a normalized value, and rounds it to the closest step of a
+    /// discrete [`Param`].
+    pub fn snap(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+
+        match self.steps {
+            Some(steps) => (value * steps as f32).round() / steps as f32,
+            None => value,
+        }
+    }
+
+    /// Formats a normalized value for display, as a percentage if no format
+    /// was set.
+    pub fn display(&self, value: f32) -> String {
+        match &self.format {
+            Some(format) => format(value),
+            None => format!("{:.0}%", value * 100.0),
+        }
+    }
+}
+
+impl fmt::Debug for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Param")
+            .field("id", &self.id)
+            .field("value", &self.value)
+            .field("default", &self.default)
+            .field("steps", &self.steps)
+            .finish()
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display(self.value))
+    }
+}
+
+/// A change to a parameter, to be reported to the host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// The user started changing the parameter.
+    Begin(ParamId),
+    /// The parameter was set to a new normalized value.
+    Set(ParamId, f32),
+    /// The user stopped changing the parameter.
+    End(ParamId),
+}
+
+/// Delivers [`Gesture`]s to a callback provided by the plugin, making sure they
+/// are paired.
+///
+/// Pass a clone of the [`Host`] to the application through its flags. The
+/// callback is called on the thread of the window.
+#[derive(Clone)]
+pub struct Host {
+    callback: Arc<dyn Fn(Gesture) + Send + Sync>,
+    active: Arc<Mutex<Vec<ParamId>>>,
+}
+
+impl Host {
+    /// Creates a new [`Host`] that reports gestures to `callback`.
+    pub fn new(callback: impl Fn(Gesture) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Arc::new(callback),
+            active: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Begins a gesture, unless one is already in progress for the parameter.
+    pub fn begin(&self, id: ParamId) {
+        let mut active = self.active();
+
+        if !active.contains(&id) {
+            active.push(id);
+            drop(active);
+
+            (self.callback)(Gesture::Begin(id));
+        }
+    }
+
+    /// Sets the normalized value of a parameter. The change is wrapped in its
+    /// own gesture if none is in progress.
+    pub fn set(&self, id: ParamId, value: f32) {
+        if self.active().contains(&id) {
+            (self.callback)(Gesture::Set(id, value));
+        } else {
+            self.change(id, value);
+        }
+    }
+
+    /// Ends the gesture in progress for the parameter, if any.
+    pub fn end(&self, id: ParamId) {
+        let mut active = self.active();
+
+        if let Some(index) = active.iter().position(|active| *active == id) {
+            let _ = active.remove(index);
+            drop(active);
+
+            (self.callback)(Gesture::End(id));
+        }
+    }
+
+    /// Sets a parameter in a single gesture, like a double-click reset does.
+    pub fn change(&self, id: ParamId, value: f32) {
+        self.begin(id);
+        (self.callback)(Gesture::Set(id, value));
+        self.end(id);
+    }
+
+    /// Ends all gestures in progress, for instance when the window closes.
+    pub fn end_all(&self) {
+        let active = std::mem::take(&mut *self.active());
+
+        for id in active {
+            (self.callback)(Gesture::End(id));
+        }
+    }
+
+    fn active(&self) -> std::sync::MutexGuard<'_, Vec<ParamId>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Host")
+            .field("active", &*self.active())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host() -> (Host, Arc<Mutex<Vec<Gesture>>>) {
+        let gestures = Arc::new(Mutex::new(Vec::new()));
+        let host = Host::new({
+            let gestures = Arc::clone(&gestures);
+
+            move |gesture| gestures.lock().unwrap().push(gesture)
+        });
+
+        (host, gestures)
+    }
+
+    #[test]
+    fn pairs_begin_and_end() {
+        let (host, gestures) = host();
+
+        host.begin(1);
+        host.begin(1);
+        host.set(1, 0.5);
+        host.end(1);
+        host.end(1);
+
+        assert_eq!(
+            *gestures.lock().unwrap(),
+            vec![Gesture::Begin(1), Gesture::Set(1, 0.5), Gesture::End(1)]
+        );
+    }
+
+    #[test]
+    fn wraps_values_set_outside_of_a_gesture() {
+        let (host, gestures) = host();
+
+        host.set(2, 0.25);
+        host.change(3, 1.0);
+
+        assert_eq!(
+            *gestures.lock().unwrap(),
+            vec![
+                Gesture::Begin(2),
+                Gesture::Set(2, 0.25),
+                Gesture::End(2),
+                Gesture::Begin(3),
+                Gesture::Set(3, 1.0),
+                Gesture::End(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_gestures_of_different_params_apart() {
+        let (host, gestures) = host();
+
+        host.begin(1);
+        host.begin(2);
+        host.end(1);
+        host.set(2, 0.5);
+        host.end(2);
+
+        assert_eq!(
+            *gestures.lock().unwrap(),
+            vec![
+                Gesture::Begin(1),
+                Gesture::Begin(2),
+                Gesture::End(1),
+                Gesture::Set(2, 0.5),
+                Gesture::End(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn end_all_ends_open_gestures_once() {
+        let (host, gestures) = host();
+
+        host.begin(1);
+        host.begin(2);
+        host.clone().end_all();
+        host.end_all();
+        host.end(1);
+
+        assert_eq!(
+            *gestures.lock().unwrap(),
+            vec![
+                Gesture::Begin(1),
+                Gesture::Begin(2),
+                Gesture::End(1),
+                Gesture::End(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn snaps_discrete_values() {
+        let param = Param::new(0, 0.0).steps(4);
+
+        assert_eq!(param.snap(0.3), 0.25);
+        assert_eq!(param.snap(1.5), 1.0);
+        assert_eq!(Param::new(0, 0.0).snap(0.3), 0.3);
+    }
+}
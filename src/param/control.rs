@@ -0,0 +1,428 @@
+//! Turn mouse input on any widget into parameter gestures.
+use crate::core::keyboard;
+use crate::core::layout::{self, Layout};
+use crate::core::mouse::{self, click};
+use crate::core::overlay;
+use crate::core::renderer;
+use crate::core::widget::tree::{self, Tree};
+use crate::core::widget::{Operation, Widget};
+use crate::core::{Clipboard, Element, Event, Length, Point, Rectangle, Shell, Size, Vector};
+use crate::event;
+use crate::settings::ScrollSettings;
+
+use super::{Host, Param, ParamId};
+
+/// How far the cursor has to be dragged to sweep the whole range of a
+/// parameter, in logical pixels.
+const DRAG_DISTANCE: f32 = 200.0;
+
+/// How much dragging is slowed down while Shift is held.
+const FINE_FACTOR: f32 = 0.1;
+
+/// How much one line of mouse wheel scrolling changes a continuous parameter.
+const WHEEL_STEP: f32 = 0.05;
+
+/// Creates a new [`Control`] for the [`Param`] with the given content.
+pub fn control<'a, Message, Theme, Renderer>(
+    param: &'a Param,
+    host: &'a Host,
+    content: impl Into<Element<'a, Message, Theme, Renderer>>,
+) -> Control<'a, Message, Theme, Renderer> {
+    Control::new(param, host, content)
+}
+
+/// A widget that changes a [`Param`] when its content is dragged, double-clicked
+/// or scrolled, and reports the changes to a [`Host`].
+///
+/// - Dragging begins a gesture when the mouse button is pressed, sets the value
+///   as the cursor moves, and ends the gesture when the button is released.
+///   Holding Shift makes the drag finer.
+/// - Double-clicking resets the parameter to its default value.
+/// - Scrolling changes the value by one step of a discrete parameter, or by a
+///   small amount of a continuous one.
+///
+/// The content only draws the parameter, for instance as a knob. Use
+/// [`Control::on_change`] to update the value shown by the application.
+#[allow(missing_debug_implementations)]
+pub struct Control<'a, Message, Theme, Renderer> {
+    param: &'a Param,
+    host: &'a Host,
+    content: Element<'a, Message, Theme, Renderer>,
+    on_change: Option<Box<dyn Fn(f32) -> Message + 'a>>,
+    horizontal: bool,
+    pixels_per_line: f32,
+}
+
+impl<'a, Message, Theme, Renderer> Control<'a, Message, Theme, Renderer> {
+    /// Creates a new [`Control`] for the [`Param`] with the given content.
+    pub fn new(
+        param: &'a Param,
+        host: &'a Host,
+        content: impl Into<Element<'a, Message, Theme, Renderer>>,
+    ) -> Self {
+        Self {
+            param,
+            host,
+            content: content.into(),
+            on_change: None,
+            horizontal: false,
+            pixels_per_line: ScrollSettings::default().pixels_per_line,
+        }
+    }
+
+    /// Sets the message to produce with the new normalized value whenever the
+    /// [`Control`] changes the [`Param`].
+    pub fn on_change(mut self, on_change: impl Fn(f32) -> Message + 'a) -> Self {
+        self.on_change = Some(Box::new(on_change));
+        self
+    }
+
+    /// Makes the [`Control`] follow horizontal drags, like a slider, instead of
+    /// vertical ones, like a knob.
+    pub fn horizontal(mut self, horizontal: bool) -> Self {
+        self.horizontal = horizontal;
+        self
+    }
+
+    /// Sets how many logical pixels of trackpad scrolling make up one line,
+    /// when the wheel reports pixels.
+    ///
+    /// Pass the [`ScrollSettings::pixels_per_line`] of the window when it
+    /// isn't the default.
+    pub fn pixels_per_line(mut self, pixels_per_line: f32) -> Self {
+        self.pixels_per_line = pixels_per_line;
+        self
+    }
+
+    /// Reports a new value to the application.
+    fn publish(&self, value: f32, shell: &mut Shell<'_, Message>) {
+        if let Some(on_change) = &self.on_change {
+            shell.publish(on_change(value));
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    drag: Option<Drag>,
+    last_click: Option<mouse::Click>,
+    modifiers: keyboard::Modifiers,
+}
+
+struct Drag {
+    /// The last position of the cursor.
+    position: Point,
+    /// The value before snapping it to a step, so slow drags still add up.
+    raw: f32,
+    /// The last value reported to the host.
+    value: f32,
+    /// The gesture begun by the drag.
+    gesture: OpenGesture,
+}
+
+/// Ends its gesture when dropped, so the gesture of a drag also ends when the
+/// widget tree is dropped in the middle of it, for instance because the window
+/// closed.
+struct OpenGesture {
+    host: Host,
+    id: ParamId,
+}
+
+impl OpenGesture {
+    fn begin(host: &Host, id: ParamId) -> Self {
+        host.begin(id);
+
+        Self {
+            host: host.clone(),
+            id,
+        }
+    }
+}
+
+impl Drop for OpenGesture {
+    fn drop(&mut self) {
+        self.host.end(self.id);
+    }
+}
+
+impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer>
+    for Control<'a, Message, Theme, Renderer>
+where
+    Renderer: crate::core::Renderer,
+{
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        self.content
+            .as_widget()
+            .layout(&mut tree.children[0], renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            viewport,
+        );
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation,
+    ) {
+        self.content
+            .as_widget()
+            .operate(&mut tree.children[0], layout, renderer, operation);
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        let id = self.param.id();
+        let state = tree.state.downcast_mut::<State>();
+
+        match &event {
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = *modifiers;
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if let Some(position) = cursor.position_over(layout.bounds()) {
+                    let click = mouse::Click::new(position, mouse::Button::Left, state.last_click);
+                    state.last_click = Some(click);
+
+                    if let click::Kind::Double = click.kind() {
+                        let value = self.param.default_value();
+
+                        self.host.change(id, value);
+                        self.publish(value, shell);
+                    } else {
+                        state.drag = Some(Drag {
+                            position,
+                            raw: self.param.value(),
+                            value: self.param.value(),
+                            gesture: OpenGesture::begin(self.host, id),
+                        });
+                    }
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                if let Some(drag) = &mut state.drag {
+                    let delta = if self.horizontal {
+                        position.x - drag.position.x
+                    } else {
+                        drag.position.y - position.y
+                    };
+                    let speed = if state.modifiers.shift() {
+                        FINE_FACTOR / DRAG_DISTANCE
+                    } else {
+                        1.0 / DRAG_DISTANCE
+                    };
+
+                    drag.position = *position;
+                    drag.raw = (drag.raw + delta * speed).clamp(0.0, 1.0);
+
+                    let value = self.param.snap(drag.raw);
+
+                    if value != drag.value {
+                        drag.value = value;
+
+                        self.host.set(drag.gesture.id, value);
+                        self.publish(value, shell);
+                    }
+
+                    return event::Status::Captured;
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if state.drag.is_some() =>
+            {
+                // Dropping the drag ends its gesture.
+                state.drag = None;
+
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta })
+                if state.drag.is_none() && cursor.is_over(layout.bounds()) =>
+            {
+                let lines = match *delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / self.pixels_per_line,
+                };
+                let step = match self.param.step_count() {
+                    Some(steps) => 1.0 / steps as f32,
+                    None if state.modifiers.shift() => WHEEL_STEP * FINE_FACTOR,
+                    None => WHEEL_STEP,
+                };
+
+                // Discrete parameters move by whole steps, however small
+                // the scroll was.
+                let change = match self.param.step_count() {
+                    Some(_) => lines.abs().ceil().copysign(lines) * step,
+                    None => lines * step,
+                };
+                let value = self.param.snap(self.param.value() + change);
+
+                if value != self.param.value() {
+                    self.host.change(id, value);
+                    self.publish(value, shell);
+                }
+
+                return event::Status::Captured;
+            }
+            _ => {}
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let state = tree.state.downcast_ref::<State>();
+
+        if state.drag.is_some() {
+            return mouse::Interaction::Grabbing;
+        }
+
+        let interaction = self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout,
+            cursor,
+            viewport,
+            renderer,
+        );
+
+        if interaction == mouse::Interaction::None && cursor.is_over(layout.bounds()) {
+            mouse::Interaction::Grab
+        } else {
+            interaction
+        }
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        self.content
+            .as_widget_mut()
+            .overlay(&mut tree.children[0], layout, renderer, translation)
+    }
+}
+
+impl<'a, Message, Theme, Renderer> From<Control<'a, Message, Theme, Renderer>>
+    for Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+    Theme: 'a,
+    Renderer: crate::core::Renderer + 'a,
+{
+    fn from(control: Control<'a, Message, Theme, Renderer>) -> Self {
+        Element::new(control)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use crate::param::Gesture;
+
+    #[test]
+    fn dropping_a_drag_ends_its_gesture() {
+        let gestures = Arc::new(Mutex::new(Vec::new()));
+        let host = Host::new({
+            let gestures = Arc::clone(&gestures);
+
+            move |gesture| gestures.lock().unwrap().push(gesture)
+        });
+
+        let state = State {
+            drag: Some(Drag {
+                position: Point::ORIGIN,
+                raw: 0.5,
+                value: 0.5,
+                gesture: OpenGesture::begin(&host, 7),
+            }),
+            ..State::default()
+        };
+
+        drop(state);
+
+        assert_eq!(
+            *gestures.lock().unwrap(),
+            vec![Gesture::Begin(7), Gesture::End(7)]
+        );
+    }
+}
@@ -0,0 +1,649 @@
+//! Send messages to a window from a realtime thread.
+//!
+//! [`WindowHandle::send_message`] allocates and may block, so it must never be
+//! called from an audio thread. A [`channel`] instead preallocates a bounded
+//! queue that the [`Sender`] pushes to without locking or allocating. Attach
+//! the [`Receiver`] to a window with [`WindowHandle::attach_receiver`], and the
+//! window delivers the queued messages to [`Application::update`] on every
+//! frame.
+//!
+//! Sending a message only moves it into the queue, but dropping a message that
+//! doesn't fit does run its destructor on the sending thread. Keep the messages
+//! sent this way free of heap allocations. Messages dropped to make room for
+//! newer ones are dropped by the window instead.
+//!
+//! For data where only the latest value matters, like the sample blocks shown
+//! by an oscilloscope, a [`snapshot`] avoids queueing altogether. Attach its
+//! [`SnapshotReader`] with [`WindowHandle::attach_snapshot`].
+//!
+//! [`WindowHandle::send_message`]: crate::window::WindowHandle::send_message
+//! [`WindowHandle::attach_receiver`]: crate::window::WindowHandle::attach_receiver
+//! [`WindowHandle::attach_snapshot`]: crate::window::WindowHandle::attach_snapshot
+//! [`Application::update`]: crate::Application::update
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// An `f32` that can be shared between threads, like a parameter value that
+/// the audio thread writes and a window watches through
+/// [`WindowSubs::params`](crate::window::WindowSubs::params).
+#[derive(Debug, Default)]
+pub struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    /// Creates a new [`AtomicF32`].
+    pub const fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    /// Loads the value.
+    pub fn load(&self, ordering: Ordering) -> f32 {
+        f32::from_bits(self.0.load(ordering))
+    }
+
+    /// Stores a value.
+    pub fn store(&self, value: f32, ordering: Ordering) {
+        self.0.store(value.to_bits(), ordering);
+    }
+}
+
+/// What happens to messages when the queue of a [`channel`] is full, and how
+/// they are delivered.
+pub enum OverflowPolicy<Message> {
+    /// Deliver only the latest messages that fit in the capacity, dropping the
+    /// older ones.
+    ///
+    /// The queue keeps room for twice the capacity, so the [`Sender`] can
+    /// keep queueing while the window drops the older messages when it drains
+    /// the queue. Only once that room runs out, because no window drains the
+    /// queue, are new messages dropped like with
+    /// [`OverflowPolicy::DropNewest`].
+    DropOldest,
+    /// Keep the queued messages and drop the new one.
+    DropNewest,
+    /// Drop the oldest messages like [`OverflowPolicy::DropOldest`], and when
+    /// delivering, skip every message that is followed by a message with the
+    /// same key according to the function. This way only the latest value of,
+    /// for instance, each parameter reaches the application. Messages without
+    /// a key are always delivered.
+    Coalesce(fn(&Message) -> Option<u64>),
+}
+
+impl<Message> Clone for OverflowPolicy<Message> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Message> Copy for OverflowPolicy<Message> {}
+
+impl<Message> fmt::Debug for OverflowPolicy<Message> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DropOldest => write!(f, "DropOldest"),
+            Self::DropNewest => write!(f, "DropNewest"),
+            Self::Coalesce(_) => write!(f, "Coalesce"),
+        }
+    }
+}
+
+/// Creates a queue with room for at least `capacity` messages, and returns
+/// both of its ends.
+pub fn channel<Message: Send>(
+    capacity: usize,
+    policy: OverflowPolicy<Message>,
+) -> (Sender<Message>, Receiver<Message>) {
+    let queue = Arc::new(Queue::new(capacity, policy));
+
+    (
+        Sender {
+            queue: Arc::clone(&queue),
+        },
+        Receiver { queue },
+    )
+}
+
+/// The sending end of a [`channel`], meant to be owned by the realtime thread.
+pub struct Sender<Message> {
+    queue: Arc<Queue<Message>>,
+}
+
+impl<Message: Send> Sender<Message> {
+    /// Queues a message without locking or allocating. Returns `false` if the
+    /// message was dropped because the queue was full.
+    ///
+    /// The sender never takes messages out of the queue, so with
+    /// [`OverflowPolicy::DropOldest`] the older messages are dropped by the
+    /// window instead.
+    pub fn send(&mut self, message: Message) -> bool {
+        // SAFETY: The sender is the only producer, since it can't be cloned and
+        // pushing borrows it mutably.
+        let queued = unsafe { self.queue.push(message) }.is_ok();
+
+        if !queued {
+            let _ = self.queue.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        queued
+    }
+
+    /// Returns the number of messages dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of messages skipped by
+    /// [`OverflowPolicy::Coalesce`].
+    pub fn coalesced(&self) -> u64 {
+        self.queue.coalesced.load(Ordering::Relaxed)
+    }
+}
+
+impl<Message> fmt::Debug for Sender<Message> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("capacity", &self.queue.capacity)
+            .finish()
+    }
+}
+
+/// The receiving end of a [`channel`].
+///
+/// The receiver can be attached to a new window whenever the editor is opened
+/// again, while the [`Sender`] stays with the realtime thread.
+pub struct Receiver<Message> {
+    queue: Arc<Queue<Message>>,
+}
+
+impl<Message: Send> Receiver<Message> {
+    /// Takes all queued messages, applying the [`OverflowPolicy`].
+    pub(crate) fn drain(&self) -> Vec<Message> {
+        let mut messages = self.queue.drain();
+
+        let key = match self.queue.policy {
+            OverflowPolicy::DropNewest => return messages,
+            OverflowPolicy::DropOldest => None,
+            OverflowPolicy::Coalesce(key) => Some(key),
+        };
+
+        if messages.len() > self.queue.capacity {
+            let excess = messages.len() - self.queue.capacity;
+
+            drop(messages.drain(..excess));
+            let _ = self
+                .queue
+                .dropped
+                .fetch_add(excess as u64, Ordering::Relaxed);
+        }
+
+        if let Some(key) = key {
+            let before = messages.len();
+            coalesce(&mut messages, key);
+
+            let _ = self
+                .queue
+                .coalesced
+                .fetch_add((before - messages.len()) as u64, Ordering::Relaxed);
+        }
+
+        messages
+    }
+}
+
+impl<Message> Clone for Receiver<Message> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+}
+
+impl<Message> fmt::Debug for Receiver<Message> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("capacity", &self.queue.capacity)
+            .finish()
+    }
+}
+
+/// Removes every message that is followed by a message with the same key.
+fn coalesce<Message>(messages: &mut Vec<Message>, key: fn(&Message) -> Option<u64>) {
+    let mut last = HashMap::new();
+
+    for (index, message) in messages.iter().enumerate() {
+        if let Some(key) = key(message) {
+            let _ = last.insert(key, index);
+        }
+    }
+
+    let mut index = 0;
+
+    messages.retain(|message| {
+        let keep = key(message).is_none_or(|key| last[&key] == index);
+        index += 1;
+
+        keep
+    });
+}
+
+/// A bounded single-producer single-consumer ring buffer.
+///
+/// The producer only ever advances `tail`, and the consumer only ever advances
+/// `head`, so each slot is owned by exactly one side at a time. Only the
+/// consumer takes messages out, and it does so while holding the `consumer`
+/// lock, since the [`Receiver`] can be cloned.
+struct Queue<Message> {
+    slots: Box<[UnsafeCell<MaybeUninit<Message>>]>,
+    mask: usize,
+    /// The number of messages delivered per drain.
+    capacity: usize,
+    /// The position of the next message to take out.
+    head: AtomicUsize,
+    /// The position of the next slot to write.
+    tail: AtomicUsize,
+    consumer: Mutex<()>,
+    policy: OverflowPolicy<Message>,
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+// SAFETY: A slot is only accessed by the side that owns it according to `head`
+// and `tail`, whose release stores publish the accesses to the other side.
+unsafe impl<Message: Send> Send for Queue<Message> {}
+unsafe impl<Message: Send> Sync for Queue<Message> {}
+
+impl<Message> Queue<Message> {
+    fn new(capacity: usize, policy: OverflowPolicy<Message>) -> Self {
+        let capacity = capacity.max(1);
+        let slots = match policy {
+            OverflowPolicy::DropNewest => capacity,
+            OverflowPolicy::DropOldest | OverflowPolicy::Coalesce(_) => capacity * 2,
+        }
+        .next_power_of_two();
+
+        Self {
+            slots: (0..slots)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            mask: slots - 1,
+            capacity: match policy {
+                OverflowPolicy::DropNewest => slots,
+                OverflowPolicy::DropOldest | OverflowPolicy::Coalesce(_) => capacity,
+            },
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            consumer: Mutex::new(()),
+            policy,
+            dropped: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// Writes a message to the next free slot, or returns it if there is none.
+    ///
+    /// # Safety
+    ///
+    /// Only one thread may push at a time.
+    unsafe fn push(&self, message: Message) -> Result<(), Message> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+
+        if tail.wrapping_sub(head) == self.slots.len() {
+            return Err(message);
+        }
+
+        // SAFETY: The slot is free, since the consumer moved past it, and only
+        // the producer writes slots.
+        let _ = unsafe { (*self.slots[tail & self.mask].get()).write(message) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Takes all queued messages out, oldest first.
+    fn drain(&self) -> Vec<Message> {
+        let _consumer = self.consumer.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let mut messages = Vec::with_capacity(tail.wrapping_sub(head));
+
+        while head != tail {
+            // SAFETY: The slot was written before `tail` was published, and
+            // only the consumer holding the lock reads slots.
+            messages.push(unsafe { (*self.slots[head & self.mask].get()).assume_init_read() });
+
+            head = head.wrapping_add(1);
+            self.head.store(head, Ordering::Release);
+        }
+
+        messages
+    }
+}
+
+impl<Message> Drop for Queue<Message> {
+    fn drop(&mut self) {
+        drop(self.drain());
+    }
+}
+
+/// Creates a triple buffer holding the latest value written by a realtime
+/// thread, and returns both of its ends. All three buffers start out as a
+/// clone of `initial`.
+pub fn snapshot<T: Clone + Send>(initial: T) -> (SnapshotWriter<T>, SnapshotReader<T>) {
+    let buffers = TripleBuffer {
+        buffers: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        middle: AtomicU8::new(1),
+        front: Mutex::new(2),
+    };
+    let buffers = Arc::new(buffers);
+
+    (
+        SnapshotWriter {
+            buffers: Arc::clone(&buffers),
+            back: 0,
+        },
+        SnapshotReader { buffers },
+    )
+}
+
+/// The writing end of a [`snapshot`], meant to be owned by the realtime thread.
+pub struct SnapshotWriter<T> {
+    buffers: Arc<TripleBuffer<T>>,
+    back: u8,
+}
+
+impl<T: Send> SnapshotWriter<T> {
+    /// Fills a buffer and publishes it as the latest value, without locking or
+    /// allocating.
+    ///
+    /// The buffer holds an older value that was written before, so `write`
+    /// should overwrite all of it. Replacing a `Vec` with a new one would free
+    /// the old one on this thread, so copy into it instead.
+    pub fn write(&mut self, write: impl FnOnce(&mut T)) {
+        // SAFETY: The back buffer is only ever accessed by the writer.
+        write(unsafe { &mut *self.buffers.buffers[usize::from(self.back)].get() });
+
+        let previous = self
+            .buffers
+            .middle
+            .swap(self.back | FRESH, Ordering::AcqRel);
+
+        self.back = previous & !FRESH;
+    }
+}
+
+impl<T> fmt::Debug for SnapshotWriter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotWriter").finish()
+    }
+}
+
+/// The reading end of a [`snapshot`].
+///
+/// Like a [`Receiver`], the reader can be attached to a new window whenever
+/// the editor is opened again.
+pub struct SnapshotReader<T> {
+    buffers: Arc<TripleBuffer<T>>,
+}
+
+impl<T: Send> SnapshotReader<T> {
+    /// Calls `read` with the latest value if it was written since the last
+    /// read, and returns its result.
+    pub fn read<R>(&self, read: impl FnOnce(&T) -> R) -> Option<R> {
+        // Only readers lock this, so the writer is never blocked.
+        let mut front = self.buffers.front.lock().unwrap_or_else(|e| e.into_inner());
+
+        if self.buffers.middle.load(Ordering::Relaxed) & FRESH == 0 {
+            return None;
+        }
+
+        let previous = self.buffers.middle.swap(*front, Ordering::AcqRel);
+        *front = previous & !FRESH;
+
+        // SAFETY: The front buffer is only accessed while holding its lock.
+        Some(read(unsafe {
+            &*self.buffers.buffers[usize::from(*front)].get()
+        }))
+    }
+}
+
+impl<T> Clone for SnapshotReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            buffers: Arc::clone(&self.buffers),
+        }
+    }
+}
+
+impl<T> fmt::Debug for SnapshotReader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotReader").finish()
+    }
+}
+
+/// The bit of [`TripleBuffer::middle`] that is set when the middle buffer holds
+/// a value that wasn't read yet.
+const FRESH: u8 = 0b100;
+
+/// Three buffers that the writer and the reader swap with the middle one, so
+/// neither of them ever waits for the other.
+struct TripleBuffer<T> {
+    buffers: [UnsafeCell<T>; 3],
+    /// The index of the middle buffer, and whether it's [`FRESH`].
+    middle: AtomicU8,
+    /// The index of the buffer that belongs to the readers.
+    front: Mutex<u8>,
+}
+
+// SAFETY: Each buffer is only accessed by whoever holds its index. Indices are
+// handed over through `middle`, which orders the accesses.
+unsafe impl<T: Send> Send for TripleBuffer<T> {}
+unsafe impl<T: Send> Sync for TripleBuffer<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    /// Keeps the tests that spawn threads short enough to run under Miri.
+    const ITERATIONS: u32 = if cfg!(miri) { 200 } else { 100_000 };
+
+    #[test]
+    fn delivers_messages_in_order() {
+        let (mut sender, receiver) = channel(4, OverflowPolicy::DropNewest);
+
+        assert!(sender.send(1));
+        assert!(sender.send(2));
+        assert_eq!(receiver.drain(), vec![1, 2]);
+
+        assert!(sender.send(3));
+        assert_eq!(receiver.drain(), vec![3]);
+        assert_eq!(receiver.drain(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_queued_messages() {
+        let (mut sender, receiver) = channel(4, OverflowPolicy::DropNewest);
+
+        for message in 0..4 {
+            assert!(sender.send(message));
+        }
+
+        assert!(!sender.send(4));
+        assert!(!sender.send(5));
+        assert_eq!(sender.dropped(), 2);
+        assert_eq!(receiver.drain(), vec![0, 1, 2, 3]);
+
+        assert!(sender.send(6));
+        assert_eq!(receiver.drain(), vec![6]);
+    }
+
+    #[test]
+    fn drop_oldest_delivers_the_latest_messages() {
+        let (mut sender, receiver) = channel(4, OverflowPolicy::DropOldest);
+
+        for message in 0..6 {
+            assert!(sender.send(message));
+        }
+
+        assert_eq!(sender.dropped(), 0);
+        assert_eq!(receiver.drain(), vec![2, 3, 4, 5]);
+        assert_eq!(sender.dropped(), 2);
+    }
+
+    #[test]
+    fn drop_oldest_drops_new_messages_when_nothing_drains() {
+        let (mut sender, receiver) = channel(4, OverflowPolicy::DropOldest);
+
+        for message in 0..8 {
+            assert!(sender.send(message));
+        }
+
+        assert!(!sender.send(8));
+        assert_eq!(sender.dropped(), 1);
+        assert_eq!(receiver.drain(), vec![4, 5, 6, 7]);
+        assert_eq!(sender.dropped(), 5);
+    }
+
+    #[test]
+    fn coalesce_keeps_the_latest_message_of_each_key() {
+        let (mut sender, receiver) = channel(
+            8,
+            OverflowPolicy::Coalesce(|(key, _): &(Option<u64>, u32)| *key),
+        );
+
+        for message in [
+            (Some(1), 0),
+            (Some(2), 1),
+            (None, 2),
+            (Some(1), 3),
+            (None, 4),
+            (Some(2), 5),
+            (Some(3), 6),
+        ] {
+            assert!(sender.send(message));
+        }
+
+        assert_eq!(
+            receiver.drain(),
+            vec![
+                (None, 2),
+                (Some(1), 3),
+                (None, 4),
+                (Some(2), 5),
+                (Some(3), 6)
+            ]
+        );
+        assert_eq!(sender.coalesced(), 2);
+        assert_eq!(sender.dropped(), 0);
+    }
+
+    #[test]
+    fn coalesce_drops_the_oldest_messages_first() {
+        let (mut sender, receiver) = channel(2, OverflowPolicy::Coalesce(|_: &u32| Some(0)));
+
+        for message in 0..4 {
+            assert!(sender.send(message));
+        }
+
+        assert_eq!(receiver.drain(), vec![3]);
+        assert_eq!(sender.dropped(), 2);
+        assert_eq!(sender.coalesced(), 1);
+    }
+
+    #[test]
+    fn dropping_the_queue_drops_its_messages() {
+        let message = Arc::new(());
+        let (mut sender, receiver) = channel(4, OverflowPolicy::DropNewest);
+
+        assert!(sender.send(Arc::clone(&message)));
+        assert!(sender.send(Arc::clone(&message)));
+        assert_eq!(Arc::strong_count(&message), 3);
+
+        drop(sender);
+        drop(receiver);
+
+        assert_eq!(Arc::strong_count(&message), 1);
+    }
+
+    #[test]
+    fn messages_cross_threads_in_order() {
+        let (mut sender, receiver) = channel(16, OverflowPolicy::DropNewest);
+
+        let producer = thread::spawn(move || {
+            for message in 0..ITERATIONS {
+                // Retry until the consumer made room.
+                while !sender.send(message) {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+
+        while expected < ITERATIONS {
+            for message in receiver.drain() {
+                assert_eq!(message, expected);
+                expected += 1;
+            }
+
+            thread::yield_now();
+        }
+
+        producer.join().unwrap();
+        assert_eq!(receiver.drain(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn snapshot_reads_only_fresh_values() {
+        let (mut writer, reader) = snapshot(0);
+
+        assert_eq!(reader.read(|value| *value), None);
+
+        writer.write(|value| *value = 1);
+        assert_eq!(reader.read(|value| *value), Some(1));
+        assert_eq!(reader.read(|value| *value), None);
+
+        writer.write(|value| *value = 2);
+        writer.write(|value| *value = 3);
+        assert_eq!(reader.read(|value| *value), Some(3));
+        assert_eq!(reader.clone().read(|value| *value), None);
+    }
+
+    #[test]
+    fn snapshot_reads_the_latest_value_across_threads() {
+        let (mut writer, reader) = snapshot([0; 4]);
+
+        let producer = thread::spawn(move || {
+            for value in 1..=ITERATIONS {
+                writer.write(|buffer| *buffer = [value; 4]);
+            }
+        });
+
+        let mut last = 0;
+
+        while last < ITERATIONS {
+            if let Some(buffer) = reader.read(|buffer| *buffer) {
+                // Values are never torn, and never older than one read before.
+                assert_eq!(buffer, [buffer[0]; 4]);
+                assert!(buffer[0] > last);
+
+                last = buffer[0];
+            }
+
+            thread::yield_now();
+        }
+
+        producer.join().unwrap();
+        assert_eq!(reader.read(|buffer| *buffer), None);
+    }
+}
@@ -1,10 +1,12 @@
 //! Configure your application.
-use std::{borrow::Cow, fmt::Debug};
+use std::{borrow::Cow, fmt::Debug, path::PathBuf, time::Duration};
 
 use baseview::{Size, WindowOpenOptions, WindowScalePolicy};
 
 pub use crate::graphics::Settings as GraphicsSettings;
 
+use crate::core::keyboard::{key::Named, Modifiers};
+
 /// The settings of an application.
 pub struct Settings {
     // /// The identifier of the application.
@@ -23,6 +25,19 @@ pub struct Settings {
 
     /// The fonts to load on boot.
     pub fonts: Vec<Cow<'static, [u8]>>,
+
+    /// The file the `chrome-trace` feature writes its trace to. When `None`, the
+    /// `CHROME_TRACE_FILE` environment variable is used, or else a file in a `traces` directory
+    /// next to the executable.
+    ///
+    /// The trace is shared by all windows of the process, so only the settings of the first
+    /// window that opens are used.
+    pub trace_file: Option<PathBuf>,
+
+    /// The path of the Unix domain socket the `automation` feature listens on, see
+    /// [`automation`](crate::automation). The socket is only created when this is set.
+    #[cfg(all(unix, feature = "automation"))]
+    pub automation_socket: Option<PathBuf>,
 }
 
 impl Default for Settings {
@@ -36,6 +51,9 @@ impl Default for Settings {
             iced_baseview: IcedBaseviewSettings::default(),
             graphics_settings: GraphicsSettings::default(),
             fonts: Default::default(),
+            trace_file: None,
+            #[cfg(all(unix, feature = "automation"))]
+            automation_socket: None,
         }
     }
 }
@@ -51,7 +69,50 @@ pub struct IcedBaseviewSettings {
     /// trigger a redraw on window visibility change (which may cause blank windows when opening or
     /// reopening the editor) and an iced limitation where it's not possible to have animations
     /// without using an asynchronous timer stream to send redraw messages to the application.
+    ///
+    /// Hosts that hide the editor can instead call
+    /// [`WindowHandle::suspend`](crate::window::WindowHandle::suspend) and
+    /// [`WindowHandle::resume`](crate::window::WindowHandle::resume), which redraws the whole
+    /// window once when it's shown again.
     pub always_redraw: bool,
+
+    /// Move keyboard focus to the next focusable widget when Tab is pressed and no widget captured
+    /// it, and to the previous one on Shift+Tab. The key is only captured when the focus actually
+    /// moved, so the host still receives it otherwise. Disable this to always leave Tab to the host.
+    pub focus_traversal: bool,
+
+    /// How mouse wheel and trackpad scrolling is delivered to the application.
+    pub scroll: ScrollSettings,
+
+    /// Merge consecutive cursor movements and wheel scrolls received between two frames into a
+    /// single event. Button and key events are never merged and keep their order. Disable this
+    /// for widgets that need every single cursor movement.
+    pub coalesce_input: bool,
+
+    /// Limit how often the window updates and presents frames, in frames per second. When `None`,
+    /// the window follows the rate at which baseview drives it.
+    pub max_frame_rate: Option<f32>,
+
+    /// The frame rate used while the window neither has keyboard focus nor the cursor, for
+    /// instance when another plugin editor is being used. When `None`, `max_frame_rate` is used.
+    pub background_frame_rate: Option<f32>,
+
+    /// Log a warning naming the slowest phase whenever a frame takes longer than this. The
+    /// timings of the last frame are also available through
+    /// [`WindowHandle::frame_metrics`](crate::window::WindowHandle::frame_metrics).
+    pub frame_budget: Option<Duration>,
+    /// Measure how many pixels the software renderer redraws every frame, and report them in
+    /// [`FrameMetrics::damaged_pixels`](crate::window::FrameMetrics::damaged_pixels) and on the
+    /// debug overlay. This diffs and copies the drawn layers once more every frame, so it's meant
+    /// for verifying that only the changed regions of the window are redrawn.
+    pub measure_damage: bool,
+
+    /// How the debug overlay of the `debug` feature is toggled and what it shows.
+    pub debug: DebugSettings,
+
+    /// Stop all subscriptions of the application while the window is suspended, and subscribe
+    /// again when it's resumed.
+    pub pause_subscriptions_while_suspended: bool,
 }
 
 impl Default for IcedBaseviewSettings {
@@ -59,6 +120,108 @@ impl Default for IcedBaseviewSettings {
         Self {
             ignore_non_modifier_keys: false,
             always_redraw: false,
+            focus_traversal: true,
+            scroll: ScrollSettings::default(),
+            coalesce_input: true,
+            max_frame_rate: None,
+            background_frame_rate: None,
+            frame_budget: None,
+            measure_damage: false,
+            debug: DebugSettings::default(),
+            pause_subscriptions_while_suspended: false,
         }
     }
 }
+
+/// The settings of the debug overlay, which is only available with the `debug` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugSettings {
+    /// The key that toggles the overlay. Many hosts use F12 themselves, so this can be changed or
+    /// disabled. The overlay can also be toggled with [`window::toggle_debug`].
+    ///
+    /// [`window::toggle_debug`]: crate::window::toggle_debug
+    pub toggle_key: Option<Named>,
+
+    /// The modifiers that have to be held together with `toggle_key`.
+    pub toggle_modifiers: Modifiers,
+
+    /// The number of recent events listed on the overlay.
+    pub event_log_size: usize,
+
+    /// Outline the layout bounds of every widget while the overlay is shown.
+    pub layout_bounds: bool,
+}
+
+impl Default for DebugSettings {
+    fn default() -> Self {
+        Self {
+            toggle_key: Some(Named::F12),
+            toggle_modifiers: Modifiers::empty(),
+            event_log_size: 10,
+            layout_bounds: false,
+        }
+    }
+}
+
+/// The scroll model applied to mouse wheel and trackpad events.
+///
+/// Mice, trackpads and platforms report scrolling in wildly different units
+/// and amounts. These settings normalize the deltas before they reach the
+/// widgets, so scrolling feels the same in every host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollSettings {
+    /// The unit scroll deltas are converted to.
+    pub unit: ScrollUnit,
+
+    /// The number of logical pixels scrolled by one line.
+    pub pixels_per_line: f32,
+
+    /// Invert the scroll direction, also known as "natural" scrolling.
+    pub invert: bool,
+
+    /// Spread scrolling over several frames instead of applying it at once.
+    pub smoothing: ScrollSmoothing,
+}
+
+impl Default for ScrollSettings {
+    fn default() -> Self {
+        Self {
+            unit: ScrollUnit::Native,
+            pixels_per_line: 20.0,
+            invert: false,
+            smoothing: ScrollSmoothing::None,
+        }
+    }
+}
+
+/// The unit of the scroll deltas delivered to the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollUnit {
+    /// Keep the unit reported by the platform. Smoothed scrolling is delivered
+    /// in pixels.
+    Native,
+    /// Deliver all scrolling in lines.
+    Lines,
+    /// Deliver all scrolling in logical pixels.
+    Pixels,
+}
+
+/// The integrator used to spread scrolling over several frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollSmoothing {
+    /// Deliver scrolling as soon as it happens.
+    None,
+    /// Move a fraction of the remaining distance every frame.
+    Smooth {
+        /// The fraction of the remaining distance covered in 1/60th of a
+        /// second, between `0.0` and `1.0`.
+        response: f32,
+    },
+    /// Keep scrolling with the speed of the last movement, slowing down over
+    /// time.
+    Kinetic {
+        /// How quickly the scrolling slows down, as the fraction of the speed
+        /// lost per second, between `0.0` and `1.0`.
+        friction: f32,
+    },
+}
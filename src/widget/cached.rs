@@ -0,0 +1,312 @@
+//! Render static content once and reuse the result.
+use std::cell::{Cell, RefCell};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::core::image::{self, Image};
+use crate::core::layout::{self, Layout};
+use crate::core::mouse;
+use crate::core::overlay;
+use crate::core::renderer;
+use crate::core::text::Renderer as _;
+use crate::core::widget::tree::{self, Tree};
+use crate::core::widget::{Operation, Widget};
+use crate::core::{
+    Clipboard, Color, Element, Event, Length, Rectangle, Renderer as _, Shell, Size, Vector,
+};
+use crate::event;
+use crate::graphics::Viewport;
+use crate::Renderer;
+
+thread_local! {
+    /// The scale factor of the window that is currently being drawn.
+    static SCALE_FACTOR: Cell<f64> = const { Cell::new(1.0) };
+}
+
+/// Sets the scale factor used to render [`Cached`] content on this thread.
+pub(crate) fn set_scale_factor(scale_factor: f64) {
+    SCALE_FACTOR.with(|cell| cell.set(scale_factor));
+}
+
+/// Creates a new [`Cached`] widget with the given content key.
+pub fn cached<'a, Message, Theme>(
+    key: impl Hash,
+    content: impl Into<Element<'a, Message, Theme, Renderer>>,
+) -> Cached<'a, Message, Theme> {
+    Cached::new(key, content)
+}
+
+/// A widget that renders its content once into an offscreen image, and draws
+/// that image until the content key changes.
+///
+/// This is meant for large static content, like the background and panel
+/// graphics of a plugin skin, so that only the dynamic widgets on top of it
+/// are rendered every frame. The image is rendered again when the key, the
+/// size of the widget, or the scale factor of the window changes.
+///
+/// The content still receives events and lays itself out as usual, but any
+/// change in its appearance that isn't reflected by the key, such as a hover
+/// effect, won't be visible.
+#[allow(missing_debug_implementations)]
+pub struct Cached<'a, Message, Theme> {
+    key: u64,
+    content: Element<'a, Message, Theme, Renderer>,
+}
+
+impl<'a, Message, Theme> Cached<'a, Message, Theme> {
+    /// Creates a new [`Cached`] widget with the given content key.
+    pub fn new(key: impl Hash, content: impl Into<Element<'a, Message, Theme, Renderer>>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+
+        Self {
+            key: hasher.finish(),
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    entry: RefCell<Option<Entry>>,
+}
+
+struct Entry {
+    key: u64,
+    scale_factor: f64,
+    size: Size,
+    handle: image::Handle,
+}
+
+impl<'a, Message, Theme> Widget<Message, Theme, Renderer> for Cached<'a, Message, Theme> {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let content = self
+            .content
+            .as_widget()
+            .layout(&mut tree.children[0], renderer, limits);
+
+        layout::Node::with_children(content.size(), vec![content])
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_ref::<State>();
+        let bounds = layout.bounds();
+        let scale_factor = SCALE_FACTOR.with(Cell::get);
+
+        let mut entry = state.entry.borrow_mut();
+
+        let is_valid = entry.as_ref().is_some_and(|entry| {
+            entry.key == self.key
+                && entry.scale_factor == scale_factor
+                && entry.size == bounds.size()
+        });
+
+        if !is_valid {
+            let content_layout = layout.children().next().expect("Content layout");
+
+            let mut offscreen = offscreen(renderer.default_font(), renderer.default_size());
+
+            offscreen.with_translation(Vector::new(-bounds.x, -bounds.y), |offscreen| {
+                self.content.as_widget().draw(
+                    &tree.children[0],
+                    offscreen,
+                    theme,
+                    style,
+                    content_layout,
+                    mouse::Cursor::Unavailable,
+                    &Rectangle::with_size(bounds.size()),
+                );
+            });
+
+            *entry = rasterize(&mut offscreen, bounds.size(), scale_factor).map(|handle| Entry {
+                key: self.key,
+                scale_factor,
+                size: bounds.size(),
+                handle,
+            });
+        }
+
+        if let Some(entry) = entry.as_ref() {
+            image::Renderer::draw_image(renderer, Image::new(entry.handle.clone()), bounds);
+        }
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation,
+    ) {
+        self.content.as_widget().operate(
+            &mut tree.children[0],
+            layout.children().next().expect("Content layout"),
+            renderer,
+            operation,
+        );
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout.children().next().expect("Content layout"),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().expect("Content layout"),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().expect("Content layout"),
+            renderer,
+            translation,
+        )
+    }
+}
+
+impl<'a, Message, Theme> From<Cached<'a, Message, Theme>> for Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+    Theme: 'a,
+{
+    fn from(cached: Cached<'a, Message, Theme>) -> Self {
+        Element::new(cached)
+    }
+}
+
+/// Rasterizes what was drawn with the `offscreen` renderer into an image of
+/// the given logical size.
+fn rasterize(offscreen: &mut Renderer, size: Size, scale_factor: f64) -> Option<image::Handle> {
+    let width = (size.width * scale_factor as f32).ceil() as u32;
+    let height = (size.height * scale_factor as f32).ceil() as u32;
+
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    let mut pixmap = tiny_skia::PixmapMut::from_bytes(&mut pixels, width, height)?;
+    let mut clip_mask = tiny_skia::Mask::new(width, height)?;
+
+    let viewport = Viewport::with_physical_size(Size::new(width, height), scale_factor);
+
+    software(offscreen).draw(
+        &mut pixmap,
+        &mut clip_mask,
+        &viewport,
+        &[Rectangle::with_size(viewport.logical_size())],
+        Color::TRANSPARENT,
+        &[] as &[String],
+    );
+
+    // The software renderer produces premultiplied BGRA pixels, while images
+    // are expected to be straight RGBA.
+    for pixel in pixels.chunks_exact_mut(4) {
+        let [b, g, r, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        let unpremultiply = |channel: u8| {
+            if a == 0 {
+                0
+            } else {
+                (u16::from(channel) * 255 / u16::from(a)).min(255) as u8
+            }
+        };
+
+        pixel.copy_from_slice(&[unpremultiply(r), unpremultiply(g), unpremultiply(b), a]);
+    }
+
+    Some(image::Handle::from_rgba(width, height, pixels))
+}
+
+#[cfg(feature = "wgpu")]
+fn offscreen(default_font: crate::Font, default_size: crate::Pixels) -> Renderer {
+    Renderer::Secondary(iced_tiny_skia::Renderer::new(default_font, default_size))
+}
+
+#[cfg(not(feature = "wgpu"))]
+fn offscreen(default_font: crate::Font, default_size: crate::Pixels) -> Renderer {
+    iced_tiny_skia::Renderer::new(default_font, default_size)
+}
+
+#[cfg(feature = "wgpu")]
+fn software(renderer: &mut Renderer) -> &mut iced_tiny_skia::Renderer {
+    match renderer {
+        Renderer::Secondary(renderer) => renderer,
+        Renderer::Primary(_) => unreachable!("Offscreen renderers are always software renderers"),
+    }
+}
+
+#[cfg(not(feature = "wgpu"))]
+fn software(renderer: &mut Renderer) -> &mut iced_tiny_skia::Renderer {
+    renderer
+}
@@ -1,4 +1,11 @@
-use std::{cell::RefCell, pin::Pin, rc::Rc, sync::Arc};
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use iced_graphics::Compositor;
 pub use iced_runtime::core::window::Id;
@@ -7,50 +14,204 @@ pub use iced_runtime::window::{
 };
 
 use baseview::{Event, EventStatus, Window, WindowHandler, WindowOpenOptions};
-use iced_runtime::futures::futures::{
-    self,
-    channel::mpsc::{self, SendError},
-};
+use iced_runtime::core::widget::Operation;
+use iced_runtime::futures::futures::channel::mpsc::{self, SendError};
 use iced_runtime::Task;
 use iced_widget::core::Size;
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
 use crate::{
-    application::{run, Application, DefaultStyle},
+    application::{run, Application, DefaultStyle, Instance},
+    realtime::AtomicF32,
+    settings::IcedBaseviewSettings,
     Renderer, Settings,
 };
 
+/// How early a frame may start to still be considered on time. Baseview's frame timer isn't
+/// precise, so without this a frame rate limit would often skip one frame too many.
+const FRAME_SLACK: Duration = Duration::from_millis(2);
+
+/// Input sent to the window from another thread through a [`WindowHandle`].
 pub enum RuntimeEvent<Message: 'static + Send> {
-    Baseview((baseview::Event, bool)),
+    Baseview(baseview::Event),
     UserEvent(iced_runtime::Action<Message>),
-    MainEventsCleared,
-    RedrawRequested,
-    WillClose,
+    Realtime(crate::realtime::Receiver<Message>),
+    Snapshot(Box<dyn Fn() -> Option<Message> + Send>),
+    Resize(Size),
+    SetScaleFactor(f64),
+    Suspend,
+    Resume,
+    Close,
 }
 
-pub(crate) struct IcedWindow<A>
+pub(crate) struct IcedWindow<A, C>
 where
     A: Application + Send + 'static,
-    // E: Executor + 'static,
-    // C: window::Compositor<Renderer = A::Renderer> + 'static,
+    C: Compositor<Renderer = Renderer> + 'static,
+    A::Theme: DefaultStyle,
 {
-    pub sender: mpsc::UnboundedSender<RuntimeEvent<A::Message>>,
-    pub instance: Pin<Box<dyn futures::Future<Output = ()>>>,
-    pub runtime_context: futures::task::Context<'static>,
-    pub runtime_rx: mpsc::UnboundedReceiver<iced_runtime::Action<A::Message>>,
-    pub window_queue_rx: mpsc::UnboundedReceiver<WindowCommand>,
-    pub event_status: Rc<RefCell<EventStatus>>,
+    instance: Instance<A, C>,
+    runtime_rx: mpsc::UnboundedReceiver<iced_runtime::Action<A::Message>>,
+    handle_rx: mpsc::UnboundedReceiver<RuntimeEvent<A::Message>>,
+    on_output: Box<dyn FnMut(A::Output) + Send>,
+    realtime_receivers: Vec<crate::realtime::Receiver<A::Message>>,
+    snapshots: Vec<Box<dyn Fn() -> Option<A::Message> + Send>>,
+
+    processed_close_signal: bool,
+
+    max_frame_rate: Option<f32>,
+    background_frame_rate: Option<f32>,
+    next_frame: Option<Instant>,
+    focused: bool,
+    cursor_inside: bool,
+
+    mouse_cursor: baseview::MouseCursor,
+    cursor_hidden: bool,
+    cursor_position: Option<baseview::Point>,
+    relative_mouse: Option<RelativeMouse>,
+
+    #[cfg(all(unix, feature = "automation"))]
+    automation: Option<crate::automation::Endpoint>,
+}
+
+/// The state of the relative mouse mode.
+#[derive(Debug, Clone, Copy)]
+struct RelativeMouse {
+    /// Where the cursor was when the mode was enabled.
+    anchor: baseview::Point,
+    /// The last position reported by the window.
+    last: baseview::Point,
+    /// The position reported to the application.
+    position: baseview::Point,
+}
 
-    pub processed_close_signal: bool,
+impl RelativeMouse {
+    fn new(anchor: baseview::Point) -> Self {
+        Self {
+            anchor,
+            last: anchor,
+            position: anchor,
+        }
+    }
+
+    /// Adds the motion from the last position reported by the window to the
+    /// position reported to the application, and returns the latter.
+    fn moved(&mut self, position: baseview::Point) -> baseview::Point {
+        self.position.x += position.x - self.last.x;
+        self.position.y += position.y - self.last.y;
+        self.last = position;
+
+        // TODO: Warp the pointer back to the anchor and set `last` to it once baseview
+        // supports moving the pointer, so the travel isn't limited by the edges of the screen.
+
+        self.position
+    }
 }
 
-impl<A> IcedWindow<A>
+impl<A, C> IcedWindow<A, C>
 where
     A: Application + Send + 'static,
+    C: Compositor<Renderer = Renderer> + 'static,
     <A as Application>::Theme: DefaultStyle,
     <A as Application>::Executor: iced_runtime::futures::Executor + 'static,
     <A as Application>::Flags: std::marker::Send,
 {
+    pub(crate) fn new(
+        instance: Instance<A, C>,
+        runtime_rx: mpsc::UnboundedReceiver<iced_runtime::Action<A::Message>>,
+        handle_rx: mpsc::UnboundedReceiver<RuntimeEvent<A::Message>>,
+        on_output: Box<dyn FnMut(A::Output) + Send>,
+        settings: &IcedBaseviewSettings,
+    ) -> Self {
+        Self {
+            instance,
+            runtime_rx,
+            handle_rx,
+            on_output,
+            realtime_receivers: Vec::new(),
+            snapshots: Vec::new(),
+
+            processed_close_signal: false,
+
+            max_frame_rate: settings.max_frame_rate,
+            background_frame_rate: settings.background_frame_rate,
+            next_frame: None,
+            focused: false,
+            cursor_inside: false,
+
+            mouse_cursor: baseview::MouseCursor::Default,
+            cursor_hidden: false,
+            cursor_position: None,
+            relative_mouse: None,
+
+            #[cfg(all(unix, feature = "automation"))]
+            automation: None,
+        }
+    }
+
+    /// Starts listening for automation commands at `path`.
+    #[cfg(all(unix, feature = "automation"))]
+    pub(crate) fn bind_automation(&mut self, path: &std::path::Path) {
+        match crate::automation::Endpoint::bind(path) {
+            Ok(endpoint) => {
+                self.automation = Some(endpoint);
+            }
+            Err(error) => {
+                log::warn!(
+                    "Failed to listen for automation at {}: {error}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    /// Handles the commands received through the automation socket.
+    #[cfg(all(unix, feature = "automation"))]
+    fn handle_automation(&mut self) {
+        use crate::automation::{Request, Response};
+
+        while let Some((request, reply)) = self.automation.as_ref().and_then(|a| a.try_recv()) {
+            let response = match request {
+                Request::Event(event) => {
+                    self.track_activity(&event);
+
+                    let _ = self.instance.handle_event(event);
+
+                    Response::Ok
+                }
+                Request::Message(text) => match self.instance.automation_message(&text) {
+                    Some(message) => {
+                        self.instance
+                            .handle_action(iced_runtime::Action::Output(message));
+
+                        Response::Ok
+                    }
+                    None => Response::Error(format!("unknown message {text}")),
+                },
+                Request::Size => {
+                    let viewport = self.instance.viewport();
+
+                    Response::Size {
+                        size: viewport.logical_size(),
+                        scale_factor: viewport.scale_factor(),
+                    }
+                }
+                Request::Screenshot => {
+                    let size = self.instance.viewport().physical_size();
+                    let rgba = self.instance.screenshot();
+
+                    if rgba.is_empty() {
+                        Response::Error(String::from("the renderer can't take screenshots"))
+                    } else {
+                        Response::Screenshot { size, rgba }
+                    }
+                }
+            };
+
+            let _ = reply.send(response);
+        }
+    }
+
     /// There's no clone implementation, but this is fine.
     fn clone_window_options(window: &WindowOpenOptions) -> WindowOpenOptions {
         WindowOpenOptions {
@@ -62,16 +223,16 @@ where
     /// Open a new window that blocks the current thread until the window is destroyed.
     ///
     /// * `settings` - The settings of the window.
-    pub fn open_blocking<C>(flags: A::Flags, settings: Settings)
-    where
-        C: Compositor<Renderer = Renderer> + 'static,
-    {
-        let (sender, receiver) = mpsc::unbounded();
+    pub fn open_blocking(flags: A::Flags, settings: Settings) {
+        // There is no handle to send input from, so the sender is dropped right away.
+        let (_, receiver) = mpsc::unbounded();
+        let shared = Arc::new(Shared::default());
 
         Window::open_blocking(
             Self::clone_window_options(&settings.window),
-            move |window: &mut baseview::Window<'_>| -> IcedWindow<A> {
-                run::<A, C>(window, flags, settings, sender, receiver).expect("Launch window")
+            move |window: &mut baseview::Window<'_>| -> IcedWindow<A, C> {
+                run::<A, C>(window, flags, settings, receiver, shared, Box::new(|_| {}))
+                    .expect("Launch window")
             },
         );
     }
@@ -80,55 +241,264 @@ where
     ///
     /// * `parent` - The parent window.
     /// * `settings` - The settings of the window.
-    pub fn open_parented<W, C>(
+    pub fn open_parented<W>(
+        parent: &W,
+        flags: A::Flags,
+        settings: Settings,
+    ) -> WindowHandle<A::Message>
+    where
+        W: HasRawWindowHandle,
+    {
+        Self::open_parented_with_output(parent, flags, settings, |_| {})
+    }
+
+    /// Open a new child window, and call `on_output` on the window thread with
+    /// every value the application emits with [`emit`].
+    ///
+    /// * `parent` - The parent window.
+    /// * `settings` - The settings of the window.
+    /// * `on_output` - The callback to hand the emitted values to.
+    pub fn open_parented_with_output<W>(
         parent: &W,
         flags: A::Flags,
         settings: Settings,
+        on_output: impl FnMut(A::Output) + Send + 'static,
     ) -> WindowHandle<A::Message>
     where
         W: HasRawWindowHandle,
-        C: Compositor<Renderer = Renderer> + 'static,
     {
         let (sender, receiver) = mpsc::unbounded();
-        let sender_clone = sender.clone();
+        let shared = Arc::new(Shared::default());
+        let shared_clone = Arc::clone(&shared);
 
         let bv_handle = Window::open_parented(
             parent,
             Self::clone_window_options(&settings.window),
-            move |window: &mut baseview::Window<'_>| -> IcedWindow<A> {
-                run::<A, C>(window, flags, settings, sender_clone, receiver).expect("Launch window")
+            move |window: &mut baseview::Window<'_>| -> IcedWindow<A, C> {
+                run::<A, C>(
+                    window,
+                    flags,
+                    settings,
+                    receiver,
+                    shared_clone,
+                    Box::new(on_output),
+                )
+                .expect("Launch window")
             },
         );
 
-        WindowHandle::new(bv_handle, sender)
+        WindowHandle::new(bv_handle, sender, shared)
+    }
+
+    /// Resizes the window to a logical size, and lays out the user interface for
+    /// it without waiting for the window to report the new size.
+    fn resize(&mut self, window: &mut Window<'_>, size: Size) {
+        let size = self.instance.constrain_size(size);
+        let physical_size = physical_size(size, self.instance.viewport().scale_factor());
+
+        self.resize_physical(window, physical_size);
+        self.instance.resize(physical_size);
+    }
+
+    /// Applies a scale factor set by the host, resizing the window so its
+    /// logical size stays the same.
+    fn set_scale_factor(&mut self, window: &mut Window<'_>, scale_factor: f64) {
+        if !(scale_factor.is_finite() && scale_factor > 0.0) {
+            log::warn!("Ignoring invalid scale factor {scale_factor}");
+
+            return;
+        }
+
+        if self.instance.viewport().scale_factor() == scale_factor {
+            return;
+        }
+
+        self.instance.set_scale_factor(scale_factor);
+
+        let physical_size = self.instance.viewport().physical_size();
+        self.resize_physical(window, physical_size);
+    }
+
+    /// Resizes the `baseview` window, which expects a size in its own logical
+    /// pixels.
+    fn resize_physical(&self, window: &mut Window<'_>, physical_size: Size<u32>) {
+        let window_scale_factor = self.instance.window_scale_factor();
+
+        window.resize(baseview::Size {
+            width: f64::from(physical_size.width) / window_scale_factor,
+            height: f64::from(physical_size.height) / window_scale_factor,
+        });
+    }
+
+    /// Resizes the window back within the size limits of the application, for
+    /// windows resized by the user or created with a size outside of them.
+    pub(crate) fn apply_size_limits(&mut self, window: &mut Window<'_>) {
+        let viewport = self.instance.viewport();
+        let size = self.instance.constrain_size(viewport.logical_size());
+
+        if physical_size(size, viewport.scale_factor()) != viewport.physical_size() {
+            self.resize(window, size);
+        }
+    }
+
+    /// Hands the values emitted by the application to the `on_output` callback.
+    fn drain_outputs(&mut self) {
+        for output in self.instance.take_outputs() {
+            match output.downcast::<A::Output>() {
+                Ok(output) => (self.on_output)(*output),
+                // `emit` was called with the type of another application.
+                Err(_) => log::warn!(
+                    "Ignoring an emitted value that isn't of type {}",
+                    std::any::type_name::<A::Output>()
+                ),
+            }
+        }
     }
 
     fn drain_window_commands(&mut self, window: &mut Window<'_>) {
-        while let Ok(Some(cmd)) = self.window_queue_rx.try_next() {
+        for cmd in self.instance.take_window_commands() {
             match cmd {
                 WindowCommand::CloseWindow => {
                     window.close();
                 }
                 WindowCommand::ResizeWindow(size) => {
-                    window.resize(baseview::Size {
-                        width: size.width as f64,
-                        height: size.height as f64,
-                    });
+                    self.resize(window, size);
                 }
                 WindowCommand::Focus => {
                     window.focus();
                 }
+                WindowCommand::ToggleDebug => {
+                    self.instance.toggle_debug();
+                }
                 WindowCommand::SetCursorIcon(cursor) => {
-                    window.set_mouse_cursor(cursor);
+                    self.mouse_cursor = cursor;
+
+                    if !self.cursor_hidden && self.relative_mouse.is_none() {
+                        window.set_mouse_cursor(cursor);
+                    }
+                }
+                WindowCommand::SetCursorHidden(hidden) => {
+                    self.cursor_hidden = hidden;
+
+                    if self.relative_mouse.is_none() {
+                        window.set_mouse_cursor(self.visible_cursor());
+                    }
+                }
+                WindowCommand::SetRelativeMouse(enabled) => {
+                    if !enabled {
+                        self.end_relative_mouse(window);
+                    } else if self.relative_mouse.is_none() {
+                        if let Some(position) = self.cursor_position {
+                            self.relative_mouse = Some(RelativeMouse::new(position));
+
+                            window.set_mouse_cursor(baseview::MouseCursor::Hidden);
+                        }
+                    }
                 }
             }
         }
+
+        self.drain_outputs();
+    }
+
+    /// Returns whether enough time passed since the last frame for the current
+    /// frame rate limit, and if so, schedules the next frame.
+    fn frame_due(&mut self) -> bool {
+        let frame_rate = if self.focused || self.cursor_inside {
+            self.max_frame_rate
+        } else {
+            self.background_frame_rate.or(self.max_frame_rate)
+        };
+
+        let Some(frame_rate) = frame_rate.filter(|frame_rate| *frame_rate > 0.0) else {
+            self.next_frame = None;
+
+            return true;
+        };
+
+        let now = Instant::now();
+        let interval = Duration::from_secs_f32(1.0 / frame_rate);
+
+        if self
+            .next_frame
+            .is_some_and(|next_frame| now + FRAME_SLACK < next_frame)
+        {
+            return false;
+        }
+
+        // Keep a steady rate, unless we fell behind by more than a frame.
+        self.next_frame = Some(match self.next_frame {
+            Some(next_frame) if now < next_frame + interval => next_frame + interval,
+            _ => now + interval,
+        });
+
+        true
+    }
+
+    /// Keeps track of whether the window is being used, for the background
+    /// frame rate.
+    fn track_activity(&mut self, event: &Event) {
+        match event {
+            Event::Window(baseview::WindowEvent::Focused) => {
+                self.focused = true;
+            }
+            Event::Window(baseview::WindowEvent::Unfocused) => {
+                self.focused = false;
+            }
+            Event::Mouse(
+                baseview::MouseEvent::CursorEntered | baseview::MouseEvent::CursorMoved { .. },
+            ) => {
+                self.cursor_inside = true;
+            }
+            Event::Mouse(baseview::MouseEvent::CursorLeft) => {
+                self.cursor_inside = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the cursor to show outside of the relative mouse mode.
+    fn visible_cursor(&self) -> baseview::MouseCursor {
+        if self.cursor_hidden {
+            baseview::MouseCursor::Hidden
+        } else {
+            self.mouse_cursor
+        }
+    }
+
+    /// Rewrites cursor movements while the relative mouse mode is active, so the
+    /// application sees the accumulated motion starting from the anchor.
+    fn apply_relative_mouse(&mut self, event: &mut Event) {
+        if let Event::Mouse(baseview::MouseEvent::CursorMoved { position, .. }) = event {
+            self.cursor_position = Some(*position);
+
+            if let Some(relative_mouse) = &mut self.relative_mouse {
+                *position = relative_mouse.moved(*position);
+            }
+        }
+    }
+
+    /// Leaves the relative mouse mode, shows the cursor again and moves the
+    /// cursor position reported to the application back to where the mode was
+    /// enabled.
+    fn end_relative_mouse(&mut self, window: &mut Window<'_>) {
+        if let Some(relative_mouse) = self.relative_mouse.take() {
+            window.set_mouse_cursor(self.visible_cursor());
+
+            let _ = self
+                .instance
+                .handle_event(Event::Mouse(baseview::MouseEvent::CursorMoved {
+                    position: relative_mouse.anchor,
+                    modifiers: keyboard_types::Modifiers::empty(),
+                }));
+        }
     }
 }
 
-impl<A> WindowHandler for IcedWindow<A>
+impl<A, C> WindowHandler for IcedWindow<A, C>
 where
     A: Application + Send + 'static,
+    C: Compositor<Renderer = Renderer> + 'static,
     <A as Application>::Theme: DefaultStyle,
     <A as Application>::Executor: iced_runtime::futures::Executor + 'static,
     <A as Application>::Flags: std::marker::Send,
@@ -138,28 +508,67 @@ where
             return;
         }
 
-        // Flush all messages. This will block until the instance is finished.
-        let _ = self.instance.as_mut().poll(&mut self.runtime_context);
+        // Handle the input sent through the `WindowHandle`.
+        while let Ok(event) = self.handle_rx.try_recv() {
+            match event {
+                RuntimeEvent::Baseview(event) => {
+                    let _ = self.instance.handle_event(event);
+                }
+                RuntimeEvent::UserEvent(action) => {
+                    self.instance.handle_action(action);
+                }
+                RuntimeEvent::Realtime(receiver) => {
+                    self.realtime_receivers.push(receiver);
+                }
+                RuntimeEvent::Snapshot(snapshot) => {
+                    self.snapshots.push(snapshot);
+                }
+                RuntimeEvent::Resize(size) => {
+                    self.resize(window, size);
+                }
+                RuntimeEvent::SetScaleFactor(scale_factor) => {
+                    self.set_scale_factor(window, scale_factor);
+                }
+                RuntimeEvent::Suspend => {
+                    self.instance.suspend();
+                }
+                RuntimeEvent::Resume => {
+                    self.instance.resume();
+                }
+                RuntimeEvent::Close => {
+                    window.close();
+                }
+            }
+        }
 
-        // Poll subscriptions and send the corresponding messages.
-        while let Ok(Some(message)) = self.runtime_rx.try_next() {
-            self.sender
-                .start_send(RuntimeEvent::UserEvent(message))
-                .expect("Send event");
+        // Handle the messages sent from realtime threads.
+        for receiver in &self.realtime_receivers {
+            for message in receiver.drain() {
+                self.instance
+                    .handle_action(iced_runtime::Action::Output(message));
+            }
         }
 
-        // Send the event to the instance.
-        self.sender
-            .start_send(RuntimeEvent::MainEventsCleared)
-            .expect("Send event");
+        // Deliver the snapshots that were written since the last frame.
+        for snapshot in &self.snapshots {
+            if let Some(message) = snapshot() {
+                self.instance
+                    .handle_action(iced_runtime::Action::Output(message));
+            }
+        }
 
-        // Send event to render the frame.
-        self.sender
-            .start_send(RuntimeEvent::RedrawRequested)
-            .expect("Send event");
+        // Handle the actions produced by tasks and subscriptions.
+        while let Ok(action) = self.runtime_rx.try_recv() {
+            self.instance.handle_action(action);
+        }
 
-        // Flush all messages. This will block until the instance is finished.
-        let _ = self.instance.as_mut().poll(&mut self.runtime_context);
+        #[cfg(all(unix, feature = "automation"))]
+        self.handle_automation();
+
+        if self.frame_due() {
+            self.instance.frame();
+            self.instance.redraw();
+        }
 
         self.drain_window_commands(window);
     }
@@ -172,28 +581,40 @@ where
         let status = if requests_exit(&event) {
             self.processed_close_signal = true;
 
-            self.sender
-                .start_send(RuntimeEvent::WillClose)
-                .expect("Send event");
-
-            // Flush all messages so the application receives the close event. This will block until the instance is finished.
-            let _ = self.instance.as_mut().poll(&mut self.runtime_context);
+            self.instance.will_close();
 
             EventStatus::Ignored
         } else {
-            // Send the event to the instance.
-            self.sender
-                .start_send(RuntimeEvent::Baseview((event, true)))
-                .expect("Send event");
+            self.track_activity(&event);
+
+            let mut event = event;
+            self.apply_relative_mouse(&mut event);
+
+            let releases_button = matches!(
+                event,
+                Event::Mouse(baseview::MouseEvent::ButtonReleased { .. })
+            );
+            let resized = matches!(event, Event::Window(baseview::WindowEvent::Resized(_)));
 
-            // Flush all messages so the application receives the event. This will block until the instance is finished.
-            let _ = self.instance.as_mut().poll(&mut self.runtime_context);
+            let status = self.instance.handle_event(event);
 
-            // TODO: make this Copy
-            *self.event_status.borrow()
+            if resized {
+                self.apply_size_limits(window);
+            }
+
+            // The relative mouse mode lasts until the drag that enabled it ends.
+            if releases_button {
+                self.end_relative_mouse(window);
+            }
+
+            status
         };
 
-        if !self.processed_close_signal {
+        if self.processed_close_signal {
+            // The window is going away, but the values emitted while closing, like the state to
+            // save, still have to reach the plugin.
+            self.drain_outputs();
+        } else {
             self.drain_window_commands(window);
         }
 
@@ -221,6 +642,107 @@ pub fn gain_focus<T>() -> Task<T> {
     iced_runtime::window::gain_focus(Id::unique())
 }
 
+/// Hides the mouse cursor until [`show_cursor`] is called. This takes
+/// precedence over the cursor requested by the widgets.
+pub fn hide_cursor<T>() -> Task<T> {
+    command(WindowCommand::SetCursorHidden(true))
+}
+
+/// Shows the mouse cursor again after [`hide_cursor`].
+pub fn show_cursor<T>() -> Task<T> {
+    command(WindowCommand::SetCursorHidden(false))
+}
+
+/// Enables the relative mouse mode, typically in response to pressing a knob or
+/// another parameter control.
+///
+/// The cursor is hidden, and the application receives the cursor movements
+/// accumulated from where the mode was enabled, so a control can follow the
+/// motion instead of the absolute position. The mode ends when a mouse button
+/// is released, at which point the cursor is shown again and the cursor
+/// position reported to the application goes back to where the mode was
+/// enabled.
+///
+/// The pointer itself isn't moved, because `baseview` can't warp it yet, so the
+/// travel is still limited by the edges of the screen.
+pub fn enable_relative_mouse<T>() -> Task<T> {
+    command(WindowCommand::SetRelativeMouse(true))
+}
+
+/// Disables the relative mouse mode before the mouse button is released.
+pub fn disable_relative_mouse<T>() -> Task<T> {
+    command(WindowCommand::SetRelativeMouse(false))
+}
+
+/// Shows or hides the debug overlay of the `debug` feature, as an alternative to
+/// the shortcut configured in [`DebugSettings`](crate::DebugSettings).
+pub fn toggle_debug<T>() -> Task<T> {
+    command(WindowCommand::ToggleDebug)
+}
+
+/// Sends a value of the [`Application::Output`] type out of the window, to
+/// the callback passed to
+/// [`open_parented_with_output`](crate::open_parented_with_output).
+///
+/// This is usually called from [`Application::update`] as
+/// `window::emit::<Self>(output)`.
+pub fn emit<A: Application>(output: A::Output) -> Task<A::Message> {
+    WindowRequest::Output(Box::new(output)).into_task()
+}
+
+/// Creates a [`Task`] that runs a [`WindowCommand`] on the `baseview` window.
+pub fn command<T>(command: WindowCommand) -> Task<T> {
+    WindowRequest::Command(command).into_task()
+}
+
+/// A [`WindowCommand`], or a value sent with [`emit`], on its way from a
+/// [`Task`] to the window.
+pub(crate) enum WindowRequest {
+    Command(WindowCommand),
+    Output(Box<dyn Any + Send>),
+}
+
+impl WindowRequest {
+    /// Creates a [`Task`] that hands the request to the window.
+    ///
+    /// `iced_runtime` doesn't know about these requests, so they travel as a
+    /// widget operation, which the window takes out again with
+    /// [`WindowRequest::take`] before it reaches any widget.
+    fn into_task<T>(self) -> Task<T> {
+        iced_runtime::task::effect(iced_runtime::Action::widget(RequestOperation(Some(self))))
+    }
+
+    /// Returns the request carried by `operation`, if it was created by
+    /// [`WindowRequest::into_task`].
+    pub(crate) fn take(operation: &mut dyn Operation) -> Option<Self> {
+        let mut request = None::<Self>;
+
+        operation.custom(&mut request, None);
+
+        request
+    }
+}
+
+/// The widget operation that carries a [`WindowRequest`]. It only gives the
+/// request away to the private state passed by [`WindowRequest::take`].
+struct RequestOperation(Option<WindowRequest>);
+
+impl Operation for RequestOperation {
+    fn container(
+        &mut self,
+        _id: Option<&crate::core::widget::Id>,
+        _bounds: crate::core::Rectangle,
+        _operate_on_children: &mut dyn FnMut(&mut dyn Operation),
+    ) {
+    }
+
+    fn custom(&mut self, state: &mut dyn Any, _id: Option<&crate::core::widget::Id>) {
+        if let Some(request) = state.downcast_mut::<Option<WindowRequest>>() {
+            *request = self.0.take();
+        }
+    }
+}
+
 /// Returns true if the provided event should cause an [`Application`] to
 /// exit.
 pub fn requests_exit(event: &baseview::Event) -> bool {
@@ -250,14 +772,20 @@ pub fn requests_exit(event: &baseview::Event) -> bool {
 pub struct WindowHandle<Message: 'static + Send> {
     bv_handle: baseview::WindowHandle,
     tx: mpsc::UnboundedSender<RuntimeEvent<Message>>,
+    shared: Arc<Shared>,
 }
 
 impl<Message: 'static + Send> WindowHandle<Message> {
     pub(crate) fn new(
         bv_handle: baseview::WindowHandle,
         tx: mpsc::UnboundedSender<RuntimeEvent<Message>>,
+        shared: Arc<Shared>,
     ) -> Self {
-        Self { bv_handle, tx }
+        Self {
+            bv_handle,
+            tx,
+            shared,
+        }
     }
 
     /// Send a custom `baseview::Event` to the window.
@@ -266,29 +794,286 @@ impl<Message: 'static + Send> WindowHandle<Message> {
     /// be used to send events from the audio thread. Use a realtime-safe ring
     /// buffer instead.
     pub fn send_baseview_event(&mut self, event: baseview::Event) -> Result<(), SendError> {
-        self.tx.start_send(RuntimeEvent::Baseview((event, false)))
+        self.tx.start_send(RuntimeEvent::Baseview(event))
     }
 
     /// Send a custom message to the window.
     ///
     /// Please note this channel is ***not*** realtime-safe and should never be
-    /// used to send events from the audio thread. Use a realtime-safe ring
-    /// buffer instead.
+    /// used to send events from the audio thread. Use a [`realtime::channel`]
+    /// instead.
+    ///
+    /// [`realtime::channel`]: crate::realtime::channel
     pub fn send_message(&mut self, msg: Message) -> Result<(), SendError> {
         self.tx
             .start_send(RuntimeEvent::UserEvent(iced_runtime::Action::Output(msg)))
     }
 
+    /// Delivers the messages sent through a [`realtime::channel`] to the
+    /// window from now on. They are handled once per frame.
+    ///
+    /// [`realtime::channel`]: crate::realtime::channel
+    pub fn attach_receiver(
+        &mut self,
+        receiver: crate::realtime::Receiver<Message>,
+    ) -> Result<(), SendError> {
+        self.tx.start_send(RuntimeEvent::Realtime(receiver))
+    }
+
+    /// Delivers the latest value written to a [`realtime::snapshot`] to the
+    /// window once per frame, as the message returned by `to_message`. Frames
+    /// without a new value don't produce a message, and don't cause a redraw.
+    ///
+    /// [`realtime::snapshot`]: crate::realtime::snapshot
+    pub fn attach_snapshot<T: Send + 'static>(
+        &mut self,
+        reader: crate::realtime::SnapshotReader<T>,
+        to_message: impl Fn(&T) -> Message + Send + 'static,
+    ) -> Result<(), SendError> {
+        self.tx.start_send(RuntimeEvent::Snapshot(Box::new(move || {
+            reader.read(&to_message)
+        })))
+    }
+
     /// Signal the window to close.
     pub fn close_window(&mut self) {
         self.bv_handle.close();
     }
 
+    /// Returns a sender for input to the window that, unlike the handle, can
+    /// be used on any thread.
+    pub(crate) fn sender(&self) -> mpsc::UnboundedSender<RuntimeEvent<Message>> {
+        self.tx.clone()
+    }
+
     /// Returns `true` if the window is still open, and `false` if the window
     /// was closed/dropped.
     pub fn is_open(&self) -> bool {
         self.bv_handle.is_open()
     }
+
+    /// Returns how many frames the window presented and skipped so far.
+    ///
+    /// A frame is only presented when something visible changed, so an idle
+    /// window should only be skipping frames.
+    pub fn frame_counters(&self) -> FrameCounters {
+        FrameCounters {
+            presented: self.shared.presented_frames.load(Ordering::Relaxed),
+            skipped: self.shared.skipped_frames.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the logical size of the window, or `None` if it hasn't been
+    /// created yet.
+    pub fn size(&self) -> Option<Size> {
+        self.viewport().map(|viewport| viewport.logical_size())
+    }
+
+    /// Resizes the window to the given logical size, like hosts do when the
+    /// user drags the border of the plugin window.
+    ///
+    /// The size is limited by the [`SizeLimits`] of the application and
+    /// rounded to whole physical pixels. Returns the logical size the window
+    /// ends up with, which the host should adopt. Use [`constrain_size`] to
+    /// only ask for that size, like CLAP's `adjust_size` does.
+    ///
+    /// The window itself then resizes to what
+    /// [`Application::constrain_size`] returns, which only differs from the
+    /// returned size if the application overrides it.
+    ///
+    /// [`constrain_size`]: Self::constrain_size
+    pub fn resize(&mut self, size: Size) -> Result<Size, SendError> {
+        let size = self.constrain_size(size);
+        let scale_factor = self
+            .viewport()
+            .map_or(1.0, |viewport| viewport.scale_factor());
+        let physical_size = physical_size(size, scale_factor);
+
+        self.tx.start_send(RuntimeEvent::Resize(size))?;
+
+        Ok(Size::new(
+            (f64::from(physical_size.width) / scale_factor) as f32,
+            (f64::from(physical_size.height) / scale_factor) as f32,
+        ))
+    }
+
+    /// Sets the scale factor of the window, as hosts do when the user moves the
+    /// plugin window to another display. The window is resized to keep its
+    /// logical size, and [`WindowSubs::on_scale_factor_changed`] is notified.
+    ///
+    /// This overrides [`Application::scale_policy`] for as long as the window
+    /// is open.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) -> Result<(), SendError> {
+        self.tx
+            .start_send(RuntimeEvent::SetScaleFactor(scale_factor))
+    }
+
+    /// Stops drawing the window, for when the host hides it
+    /// without closing it. [`WindowSubs::on_suspend`] is notified.
+    ///
+    /// Held mouse buttons are released. Messages are still handled on every
+    /// frame, but the user interface isn't rebuilt or drawn, and input events
+    /// don't reach the widgets.
+    pub fn suspend(&mut self) -> Result<(), SendError> {
+        self.tx.start_send(RuntimeEvent::Suspend)
+    }
+
+    /// Resumes the window after [`suspend`](Self::suspend), and redraws all of
+    /// it. [`WindowSubs::on_resume`] is notified.
+    pub fn resume(&mut self) -> Result<(), SendError> {
+        self.tx.start_send(RuntimeEvent::Resume)
+    }
+
+    /// Returns the logical size the window would take if the host resized it
+    /// to `requested`, without resizing it.
+    ///
+    /// This is answered right away on the calling thread, from the
+    /// [`SizeLimits`] of the application only. An overridden
+    /// [`Application::constrain_size`] is not called, because the application
+    /// lives on the thread of the window.
+    pub fn constrain_size(&self, requested: Size) -> Size {
+        self.shared
+            .size_limits
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .constrain(requested)
+    }
+
+    fn viewport(&self) -> Option<crate::Viewport> {
+        self.shared
+            .viewport
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clone()
+    }
+
+    /// Returns how long the phases of the last frame the window drew took.
+    pub fn frame_metrics(&self) -> FrameMetrics {
+        *self
+            .shared
+            .frame_metrics
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// The number of frames a window presented and skipped since it was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameCounters {
+    /// The frames that were rendered and presented.
+    pub presented: u64,
+    /// The frames that were skipped because nothing changed.
+    pub skipped: u64,
+}
+
+/// How long the phases of a frame took, as measured by the runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameMetrics {
+    /// Building the widgets with [`Application::view`].
+    pub view: Duration,
+    /// Laying out the widgets.
+    pub layout: Duration,
+    /// Handling messages with [`Application::update`].
+    pub update: Duration,
+    /// Drawing the widgets.
+    pub draw: Duration,
+    /// Rendering and presenting the frame.
+    pub present: Duration,
+    /// The number of events processed by the user interface.
+    pub events: usize,
+    /// The number of physical pixels the software renderer redrew, compared
+    /// to the previous frame.
+    ///
+    /// This is only measured with
+    /// [`IcedBaseviewSettings::measure_damage`](crate::settings::IcedBaseviewSettings::measure_damage),
+    /// and is `None` otherwise or with the `wgpu` renderer.
+    pub damaged_pixels: Option<u64>,
+}
+
+impl FrameMetrics {
+    /// Returns the time spent in all phases together.
+    pub fn total(&self) -> Duration {
+        self.view + self.layout + self.update + self.draw + self.present
+    }
+
+    /// Returns the name and duration of the phase that took the longest.
+    pub fn slowest_phase(&self) -> (&'static str, Duration) {
+        [
+            ("view", self.view),
+            ("layout", self.layout),
+            ("update", self.update),
+            ("draw", self.draw),
+            ("present", self.present),
+        ]
+        .into_iter()
+        .max_by_key(|(_, duration)| *duration)
+        .unwrap_or(("view", Duration::ZERO))
+    }
+}
+
+/// State shared between a window and its [`WindowHandle`].
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    pub presented_frames: AtomicU64,
+    pub skipped_frames: AtomicU64,
+    pub frame_metrics: Mutex<FrameMetrics>,
+    pub viewport: Mutex<Option<crate::Viewport>>,
+    pub size_limits: Mutex<SizeLimits>,
+}
+
+/// The limits an [`Application`] puts on the size of its window.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SizeLimits {
+    /// See [`Application::min_size`].
+    pub min: Option<Size>,
+    /// See [`Application::max_size`].
+    pub max: Option<Size>,
+    /// See [`Application::aspect_ratio`].
+    pub aspect_ratio: Option<f32>,
+}
+
+impl SizeLimits {
+    /// Returns the limits of an [`Application`].
+    pub fn of<A: Application>(application: &A) -> Self {
+        Self {
+            min: application.min_size(),
+            max: application.max_size(),
+            aspect_ratio: application.aspect_ratio(),
+        }
+    }
+
+    /// Returns the size closest to `requested` that satisfies the limits. When
+    /// the aspect ratio can't be kept within the minimum and maximum size, the
+    /// minimum and maximum size win.
+    pub fn constrain(&self, requested: Size) -> Size {
+        let clamp = |size: Size| {
+            let size = self.min.map_or(size, |min| size.max(min));
+
+            self.max.map_or(size, |max| size.min(max))
+        };
+
+        let size = clamp(requested);
+
+        match self
+            .aspect_ratio
+            .filter(|ratio| ratio.is_finite() && *ratio > 0.0)
+        {
+            // Shrink the dimension that is too large for the ratio.
+            Some(ratio) if size.width > size.height * ratio => {
+                clamp(Size::new(size.height * ratio, size.height))
+            }
+            Some(ratio) => clamp(Size::new(size.width, size.width / ratio)),
+            None => size,
+        }
+    }
+}
+
+/// Returns the physical size of a window with the given logical size.
+fn physical_size(size: Size, scale_factor: f64) -> Size<u32> {
+    Size::new(
+        (f64::from(size.width) * scale_factor).round().max(1.0) as u32,
+        (f64::from(size.height) * scale_factor).round().max(1.0) as u32,
+    )
 }
 
 unsafe impl<Message: 'static + Send> HasRawWindowHandle for WindowHandle<Message> {
@@ -303,37 +1088,59 @@ pub enum WindowCommand {
     ResizeWindow(crate::core::Size),
     Focus,
     SetCursorIcon(baseview::MouseCursor),
+    SetCursorHidden(bool),
+    SetRelativeMouse(bool),
+    ToggleDebug,
 }
 
 /// Used to request things from the `baseview` window.
+#[derive(Debug, Default)]
 pub struct WindowQueue {
-    tx: mpsc::UnboundedSender<WindowCommand>,
+    commands: Vec<WindowCommand>,
+    outputs: Vec<Box<dyn Any + Send>>,
 }
 
 impl WindowQueue {
-    pub fn new() -> (Self, mpsc::UnboundedReceiver<WindowCommand>) {
-        let (tx, rx) = mpsc::unbounded();
-
-        (Self { tx }, rx)
+    pub fn new() -> Self {
+        Self::default()
     }
 
     /// Resize the current application window.
-    pub fn resize_window(&mut self, size: crate::core::Size) -> Result<(), SendError> {
-        self.tx.start_send(WindowCommand::ResizeWindow(size))
+    pub fn resize_window(&mut self, size: crate::core::Size) {
+        self.commands.push(WindowCommand::ResizeWindow(size));
     }
 
     /// Quit the current application and close the window.
-    pub fn close_window(&mut self) -> Result<(), SendError> {
-        self.tx.start_send(WindowCommand::CloseWindow)
+    pub fn close_window(&mut self) {
+        self.commands.push(WindowCommand::CloseWindow);
     }
 
     /// Request to focus the application window.
-    pub fn focus(&mut self) -> Result<(), SendError> {
-        self.tx.start_send(WindowCommand::Focus)
+    pub fn focus(&mut self) {
+        self.commands.push(WindowCommand::Focus);
     }
 
-    pub fn set_mouse_cursor(&mut self, cursor: baseview::MouseCursor) -> Result<(), SendError> {
-        self.tx.start_send(WindowCommand::SetCursorIcon(cursor))
+    pub fn set_mouse_cursor(&mut self, cursor: baseview::MouseCursor) {
+        self.commands.push(WindowCommand::SetCursorIcon(cursor));
+    }
+
+    /// Queue a [`WindowCommand`] created through [`command`], or a value sent
+    /// with [`emit`].
+    pub(crate) fn request(&mut self, request: WindowRequest) {
+        match request {
+            WindowRequest::Command(command) => self.commands.push(command),
+            WindowRequest::Output(output) => self.outputs.push(output),
+        }
+    }
+
+    /// Takes all commands queued since the last call.
+    pub(crate) fn take(&mut self) -> Vec<WindowCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    /// Takes all values emitted through [`emit`] since the last call.
+    pub(crate) fn take_outputs(&mut self) -> Vec<Box<dyn Any + Send>> {
+        std::mem::take(&mut self.outputs)
     }
 }
 
@@ -344,6 +1151,29 @@ pub struct WindowSubs<Message> {
     pub on_frame: Option<Arc<dyn Fn() -> Option<Message>>>,
     /// The message to send when the window is about to close.
     pub on_window_will_close: Option<Arc<dyn Fn() -> Option<Message>>>,
+    /// The message to send after Tab or Shift+Tab moved the keyboard focus. The
+    /// argument is the [`widget::Id`] of the newly focused widget, if it has one.
+    ///
+    /// This can be used to draw a visible focus indicator around that widget.
+    ///
+    /// [`widget::Id`]: crate::core::widget::Id
+    pub on_focus_changed: Option<Arc<dyn Fn(Option<crate::core::widget::Id>) -> Option<Message>>>,
+    /// Values shared with other threads, like the parameters that the host
+    /// automates. They are compared with the values seen on the last frame, and
+    /// the window is only redrawn when any of them changed.
+    pub params: Option<Arc<[AtomicF32]>>,
+    /// The message to send when any of the [`params`](Self::params) changed.
+    /// The argument holds the indices of the changed values.
+    pub on_params_changed: Option<Arc<dyn Fn(Vec<usize>) -> Option<Message>>>,
+    /// The message to send when the host changed the scale factor with
+    /// [`WindowHandle::set_scale_factor`].
+    pub on_scale_factor_changed: Option<Arc<dyn Fn(f64) -> Option<Message>>>,
+    /// The message to send when the host suspended the window with
+    /// [`WindowHandle::suspend`].
+    pub on_suspend: Option<Arc<dyn Fn() -> Option<Message>>>,
+    /// The message to send when the host resumed the window with
+    /// [`WindowHandle::resume`].
+    pub on_resume: Option<Arc<dyn Fn() -> Option<Message>>>,
 }
 
 impl<Message> Default for WindowSubs<Message> {
@@ -351,6 +1181,172 @@ impl<Message> Default for WindowSubs<Message> {
         WindowSubs {
             on_frame: None,
             on_window_will_close: None,
+            on_focus_changed: None,
+            params: None,
+            on_params_changed: None,
+            on_scale_factor_changed: None,
+            on_suspend: None,
+            on_resume: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use iced_runtime::futures::futures::{executor, StreamExt};
+
+    /// Runs `task` to completion and queues the requests of its actions.
+    fn run(task: Task<()>) -> WindowQueue {
+        let mut window_queue = WindowQueue::new();
+        let actions: Vec<_> = iced_runtime::task::into_stream(task)
+            .map(|stream| executor::block_on(stream.collect()))
+            .unwrap_or_default();
+
+        for action in actions {
+            match action {
+                iced_runtime::Action::Widget(mut operation) => {
+                    window_queue.request(
+                        WindowRequest::take(operation.as_mut()).expect("a window request"),
+                    );
+                }
+                _ => panic!("expected a widget operation"),
+            }
+        }
+
+        window_queue
+    }
+
+    #[test]
+    fn commands_travel_in_their_task() {
+        let mut window_queue = run(Task::none()
+            .chain(command(WindowCommand::Focus))
+            .chain(hide_cursor()));
+
+        assert!(matches!(
+            window_queue.take().as_slice(),
+            [WindowCommand::Focus, WindowCommand::SetCursorHidden(true)]
+        ));
+    }
+
+    #[test]
+    fn requests_are_only_given_to_the_window() {
+        let mut operation = crate::core::widget::operation::focusable::focus::<()>(
+            crate::core::widget::Id::unique(),
+        );
+
+        assert!(WindowRequest::take(&mut operation).is_none());
+
+        let mut window_queue = run(command(WindowCommand::CloseWindow));
+        assert!(window_queue.take_outputs().is_empty());
+        assert!(matches!(
+            window_queue.take().as_slice(),
+            [WindowCommand::CloseWindow]
+        ));
+    }
+
+    #[test]
+    fn relative_mouse_accumulates_motion_from_the_anchor() {
+        let mut relative_mouse = RelativeMouse::new(baseview::Point::new(50.0, 50.0));
+
+        assert_eq!(
+            relative_mouse.moved(baseview::Point::new(60.0, 45.0)),
+            baseview::Point::new(60.0, 45.0)
+        );
+        assert_eq!(
+            relative_mouse.moved(baseview::Point::new(70.0, 40.0)),
+            baseview::Point::new(70.0, 40.0)
+        );
+        assert_eq!(
+            relative_mouse.moved(baseview::Point::new(65.0, 40.0)),
+            baseview::Point::new(65.0, 40.0)
+        );
+        assert_eq!(relative_mouse.anchor, baseview::Point::new(50.0, 50.0));
+    }
+
+    #[test]
+    fn size_limits_clamp_to_min_and_max() {
+        let limits = SizeLimits {
+            min: Some(Size::new(100.0, 50.0)),
+            max: Some(Size::new(400.0, 300.0)),
+            aspect_ratio: None,
+        };
+
+        assert_eq!(
+            limits.constrain(Size::new(10.0, 10.0)),
+            Size::new(100.0, 50.0)
+        );
+        assert_eq!(
+            limits.constrain(Size::new(1000.0, 1000.0)),
+            Size::new(400.0, 300.0)
+        );
+        assert_eq!(
+            limits.constrain(Size::new(200.0, 100.0)),
+            Size::new(200.0, 100.0)
+        );
+        assert_eq!(
+            SizeLimits::default().constrain(Size::new(1.0, 2.0)),
+            Size::new(1.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn size_limits_keep_the_aspect_ratio() {
+        let limits = SizeLimits {
+            aspect_ratio: Some(2.0),
+            ..SizeLimits::default()
+        };
+
+        assert_eq!(
+            limits.constrain(Size::new(400.0, 100.0)),
+            Size::new(200.0, 100.0)
+        );
+        assert_eq!(
+            limits.constrain(Size::new(100.0, 400.0)),
+            Size::new(100.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn size_limits_prefer_min_and_max_over_the_aspect_ratio() {
+        let limits = SizeLimits {
+            min: Some(Size::new(100.0, 100.0)),
+            max: None,
+            aspect_ratio: Some(4.0),
+        };
+
+        assert_eq!(
+            limits.constrain(Size::new(200.0, 200.0)),
+            Size::new(200.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn size_limits_ignore_invalid_aspect_ratios() {
+        for aspect_ratio in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let limits = SizeLimits {
+                aspect_ratio: Some(aspect_ratio),
+                ..SizeLimits::default()
+            };
+
+            assert_eq!(
+                limits.constrain(Size::new(300.0, 200.0)),
+                Size::new(300.0, 200.0)
+            );
         }
     }
+
+    #[test]
+    fn physical_sizes_are_rounded_and_never_empty() {
+        assert_eq!(
+            physical_size(Size::new(100.4, 50.6), 1.0),
+            Size::new(100, 51)
+        );
+        assert_eq!(
+            physical_size(Size::new(100.0, 50.0), 1.5),
+            Size::new(150, 75)
+        );
+        assert_eq!(physical_size(Size::new(0.0, 0.2), 2.0), Size::new(1, 1));
+    }
 }